@@ -21,7 +21,8 @@ impl AABB {
         &self.max
     }
 
-    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+    /// Distance at which the ray enters the box, clamped to `t_min`.
+    pub fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
         let mut min_t = t_min;
         let mut max_t = t_max;
 
@@ -43,11 +44,19 @@ impl AABB {
             max_t = t1.min(max_t);
 
             if max_t <= min_t {
-                return false;
+                return None;
             }
         }
 
-        true
+        Some(min_t)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let x = self.max.x() - self.min.x();
+        let y = self.max.y() - self.min.y();
+        let z = self.max.z() - self.min.z();
+
+        2.0 * (x * y + y * z + z * x)
     }
 
     pub fn surrounding_box(&self, other: &Self) -> AABB {
@@ -0,0 +1,403 @@
+use std::{convert::TryFrom, path::Path, result};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    config::{AnimationConfig, CameraConfig},
+    error::{Error, Result},
+    vec3::Vec3,
+};
+
+/// How a value eases from one keyframe to the next. Written as `"linear"`,
+/// `"smoothstep"` or the four bezier control points.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(try_from = "CurveRepr", into = "CurveRepr")]
+pub enum Curve {
+    #[default]
+    Linear,
+    /// Starts and stops gently, with `3f² - 2f³`.
+    Smoothstep,
+    /// A CSS style `cubic-bezier(x1, y1, x2, y2)`. The control points are kept in
+    /// 0-1 so the value never overshoots either keyframe.
+    Bezier([f64; 4]),
+}
+
+/// TOML can't hold enums with data, other than as tables that don't survive being
+/// written back out, so curves are told apart by shape instead.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum CurveRepr {
+    Name(String),
+    Bezier([f64; 4]),
+}
+
+impl TryFrom<CurveRepr> for Curve {
+    type Error = String;
+
+    fn try_from(repr: CurveRepr) -> result::Result<Self, Self::Error> {
+        match repr {
+            CurveRepr::Name(name) => match name.as_str() {
+                "linear" => Ok(Curve::Linear),
+                "smoothstep" => Ok(Curve::Smoothstep),
+                _ => Err(format!(
+                    "unknown curve `{}`, expected `linear`, `smoothstep` or four bezier control points",
+                    name
+                )),
+            },
+            CurveRepr::Bezier(points) => Ok(Curve::Bezier(points)),
+        }
+    }
+}
+
+impl From<Curve> for CurveRepr {
+    fn from(curve: Curve) -> Self {
+        match curve {
+            Curve::Linear => CurveRepr::Name("linear".to_string()),
+            Curve::Smoothstep => CurveRepr::Name("smoothstep".to_string()),
+            Curve::Bezier(points) => CurveRepr::Bezier(points),
+        }
+    }
+}
+
+impl Curve {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Curve::Linear | Curve::Smoothstep => true,
+            Curve::Bezier(points) => points.iter().all(|point| (0.0..=1.0).contains(point)),
+        }
+    }
+
+    /// Maps `f`, how far through the time between two keyframes in 0-1, to how
+    /// far between their values. The result never leaves 0-1 and never goes back,
+    /// which bounding moving shapes relies on.
+    pub fn ease(self, f: f64) -> f64 {
+        let f = f.clamp(0.0, 1.0);
+
+        match self {
+            Curve::Linear => f,
+            Curve::Smoothstep => f * f * (3.0 - 2.0 * f),
+            Curve::Bezier([x1, y1, x2, y2]) => {
+                let bezier = |a: f64, b: f64, s: f64| {
+                    let r = 1.0 - s;
+                    3.0 * r * r * s * a + 3.0 * r * s * s * b + s * s * s
+                };
+
+                // x rises monotonically with control points in 0-1, so bisect for
+                // the parameter that lands on `f`
+                let (mut low, mut high) = (0.0, 1.0);
+                for _ in 0..32 {
+                    let middle = 0.5 * (low + high);
+                    if bezier(x1, x2, middle) < f {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+
+                bezier(y1, y2, 0.5 * (low + high))
+            }
+        }
+    }
+}
+
+/// Finds the keyframes either side of `time` in `keyframes`, which are sorted by
+/// `time_of`, along with how far through the gap between them it is. Before the
+/// first and after the last keyframe both are the same one.
+pub fn locate<K, F: Fn(&K) -> f64>(keyframes: &[K], time: f64, time_of: F) -> (usize, usize, f64) {
+    let next = keyframes.partition_point(|keyframe| time_of(keyframe) <= time);
+
+    if next == 0 {
+        (0, 0, 0.0)
+    } else if next == keyframes.len() {
+        (next - 1, next - 1, 0.0)
+    } else {
+        let (start, end) = (time_of(&keyframes[next - 1]), time_of(&keyframes[next]));
+        (next - 1, next, (time - start) / (end - start))
+    }
+}
+
+/// Where the camera is at one point in an animation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraPose {
+    pub look_from: Vec3,
+    pub look_at: Vec3,
+    /// Vertical, in degrees.
+    pub fov: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct CameraKeyframe {
+    time: f64,
+    pose: CameraPose,
+    curve: Curve,
+}
+
+impl CameraKeyframe {
+    /// `curve` eases the way to the next keyframe.
+    pub fn new(time: f64, pose: CameraPose, curve: Curve) -> Self {
+        Self { time, pose, curve }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CameraTrack {
+    keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraTrack {
+    /// Keyframes can be in any order, but there has to be at least one.
+    pub fn new(mut keyframes: Vec<CameraKeyframe>) -> Self {
+        assert!(!keyframes.is_empty(), "a camera track needs a keyframe");
+        keyframes.sort_by(|a, b| a.time.partial_cmp(&b.time).expect("keyframe time is NaN"));
+
+        Self { keyframes }
+    }
+
+    /// Keyframes only need to give what changes: anything left out is carried over
+    /// from the keyframe before, and for the first from `[camera]`.
+    pub fn from_config(
+        camera_config: &CameraConfig,
+        animation_config: &AnimationConfig,
+    ) -> Result<Self> {
+        let mut pose = CameraPose {
+            look_from: camera_config.look_from().clone(),
+            look_at: camera_config.look_at().clone(),
+            fov: camera_config.fov(),
+        };
+
+        let mut keyframe_configs: Vec<_> = animation_config.camera().iter().collect();
+        if let Some(keyframe_config) = keyframe_configs.iter().find(|k| !k.time().is_finite()) {
+            let message = format!(
+                "Camera keyframe time {} isn't a number",
+                keyframe_config.time()
+            );
+            return Err(Error::scene(message));
+        }
+        keyframe_configs.sort_by(|a, b| a.time().partial_cmp(&b.time()).expect("checked above"));
+
+        let mut keyframes = Vec::with_capacity(keyframe_configs.len());
+        for keyframe_config in keyframe_configs {
+            if !keyframe_config.curve().is_valid() {
+                let message = format!(
+                    "Camera keyframe at {}s has bezier control points outside 0-1",
+                    keyframe_config.time()
+                );
+                return Err(Error::scene(message));
+            }
+
+            if let Some(look_from) = keyframe_config.look_from() {
+                pose.look_from = look_from.clone();
+            }
+            if let Some(look_at) = keyframe_config.look_at() {
+                pose.look_at = look_at.clone();
+            }
+            if let Some(fov) = keyframe_config.fov() {
+                pose.fov = fov;
+            }
+
+            let curve = keyframe_config.curve();
+            keyframes.push(CameraKeyframe::new(
+                keyframe_config.time(),
+                pose.clone(),
+                curve,
+            ));
+        }
+
+        // Without keyframes the camera holds still
+        if keyframes.is_empty() {
+            keyframes.push(CameraKeyframe::new(0.0, pose, Curve::Linear));
+        }
+
+        Ok(Self::new(keyframes))
+    }
+
+    pub fn pose(&self, time: f64) -> CameraPose {
+        let (from, to, f) = locate(&self.keyframes, time, |keyframe| keyframe.time);
+        let (from, to) = (&self.keyframes[from], &self.keyframes[to]);
+
+        let f = from.curve.ease(f);
+        let lerp = |a: &Vec3, b: &Vec3| a + f * (b - a);
+
+        CameraPose {
+            look_from: lerp(&from.pose.look_from, &to.pose.look_from),
+            look_at: lerp(&from.pose.look_at, &to.pose.look_at),
+            fov: from.pose.fov + f * (to.pose.fov - from.pose.fov),
+        }
+    }
+}
+
+/// Where to write `frame`. A run of `#` in the file name is replaced by the frame
+/// number padded to its length, otherwise the number goes before the extension.
+pub fn frame_path(path: &str, frame: usize) -> String {
+    if let Some(start) = path.rfind('#') {
+        let end = start + 1;
+        let start = path[..end].trim_end_matches('#').len();
+        let number = format!("{:0width$}", frame, width = end - start);
+
+        return format!("{}{}{}", &path[..start], number, &path[end..]);
+    }
+
+    let extension_start = Path::new(path)
+        .extension()
+        .map(|extension| path.len() - extension.len() - 1)
+        .unwrap_or(path.len());
+
+    format!(
+        "{}_{:04}{}",
+        &path[..extension_start],
+        frame,
+        &path[extension_start..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves_clamp_outside_the_gap() {
+        for &curve in &[
+            Curve::Linear,
+            Curve::Smoothstep,
+            Curve::Bezier([0.4, 0.0, 0.2, 1.0]),
+        ] {
+            assert_eq!(curve.ease(-0.5), curve.ease(0.0));
+            assert_eq!(curve.ease(1.5), curve.ease(1.0));
+            assert!(curve.ease(0.0).abs() < 1e-9);
+            assert!((curve.ease(1.0) - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn bezier_finds_the_point_at_f() {
+        // Control points on the diagonal make a straight line
+        let diagonal = Curve::Bezier([0.25, 0.25, 0.75, 0.75]);
+        for &f in &[0.1, 0.3, 0.5, 0.9] {
+            assert!((diagonal.ease(f) - f).abs() < 1e-6);
+        }
+
+        // Symmetric about the middle, and eased in and out
+        let ease_in_out = Curve::Bezier([0.42, 0.0, 0.58, 1.0]);
+        assert!((ease_in_out.ease(0.5) - 0.5).abs() < 1e-6);
+        assert!((ease_in_out.ease(0.2) + ease_in_out.ease(0.8) - 1.0).abs() < 1e-6);
+        assert!(ease_in_out.ease(0.2) < 0.2);
+    }
+
+    #[test]
+    fn valid_curves_never_overshoot_or_go_back() {
+        let curves = [
+            Curve::Smoothstep,
+            Curve::Bezier([0.0, 1.0, 1.0, 0.0]),
+            Curve::Bezier([1.0, 0.0, 0.0, 1.0]),
+            Curve::Bezier([0.9, 0.0, 1.0, 0.1]),
+            Curve::Bezier([0.1, 1.0, 0.0, 0.9]),
+        ];
+        for &curve in &curves {
+            let mut previous = 0.0;
+            for step in 0..=1000 {
+                let eased = curve.ease(step as f64 / 1000.0);
+                assert!((0.0..=1.0).contains(&eased), "{:?}", curve);
+                assert!(
+                    eased >= previous - 1e-9,
+                    "{:?} goes back at {}",
+                    curve,
+                    step
+                );
+                previous = eased;
+            }
+        }
+    }
+
+    #[test]
+    fn curves_outside_the_unit_square_are_invalid() {
+        assert!(Curve::Bezier([0.0, 1.0, 1.0, 0.0]).is_valid());
+        assert!(!Curve::Bezier([0.5, -0.1, 0.5, 1.0]).is_valid());
+        assert!(!Curve::Bezier([0.5, 0.0, 1.5, 1.0]).is_valid());
+    }
+
+    #[test]
+    fn locate_finds_the_keyframes_around_a_time() {
+        let times = [1.0, 2.0, 4.0];
+        let locate = |time| locate(&times, time, |&time| time);
+
+        assert_eq!(locate(0.0), (0, 0, 0.0));
+        assert_eq!(locate(1.0), (0, 1, 0.0));
+        assert_eq!(locate(1.5), (0, 1, 0.5));
+        assert_eq!(locate(3.0), (1, 2, 0.5));
+        assert_eq!(locate(4.0), (2, 2, 0.0));
+        assert_eq!(locate(9.0), (2, 2, 0.0));
+    }
+
+    #[test]
+    fn frame_path_fills_runs_of_hashes() {
+        assert_eq!(frame_path("out/frame_###.png", 7), "out/frame_007.png");
+        assert_eq!(frame_path("out/#.png", 12), "out/12.png");
+        assert_eq!(frame_path("out/a#b##.png", 3), "out/a#b03.png");
+    }
+
+    #[test]
+    fn frame_path_numbers_before_the_extension() {
+        assert_eq!(frame_path("out/frame.png", 7), "out/frame_0007.png");
+        assert_eq!(frame_path("out/frame", 7), "out/frame_0007");
+        assert_eq!(frame_path("out.d/frame", 7), "out.d/frame_0007");
+    }
+
+    #[test]
+    fn camera_track_carries_unset_values_forward() {
+        let camera_config: CameraConfig = toml::from_str(
+            r#"
+            fov = 40.0
+            look_from = { x = 0.0, y = 0.0, z = 10.0 }
+            look_at = { x = 0.0, y = 0.0, z = 0.0 }
+            "#,
+        )
+        .unwrap();
+        let animation_config: AnimationConfig = toml::from_str(
+            r#"
+            frames = 10
+            camera = [
+                { time = 2.0, fov = 20.0 },
+                { time = 0.0, look_at = { x = 1.0, y = 0.0, z = 0.0 } },
+            ]
+            "#,
+        )
+        .unwrap();
+        let track = CameraTrack::from_config(&camera_config, &animation_config).unwrap();
+
+        let look_from = Vec3::new(0.0, 0.0, 10.0);
+        let look_at = Vec3::new(1.0, 0.0, 0.0);
+        assert_eq!(
+            track.pose(0.0),
+            CameraPose {
+                look_from: look_from.clone(),
+                look_at: look_at.clone(),
+                fov: 40.0,
+            }
+        );
+        assert_eq!(
+            track.pose(1.0),
+            CameraPose {
+                look_from: look_from.clone(),
+                look_at: look_at.clone(),
+                fov: 30.0,
+            }
+        );
+        assert_eq!(
+            track.pose(5.0),
+            CameraPose {
+                look_from,
+                look_at,
+                fov: 20.0,
+            }
+        );
+    }
+
+    #[test]
+    fn camera_track_rejects_nan_times() {
+        let animation_config: AnimationConfig =
+            toml::from_str("frames = 1\ncamera = [{ time = nan }]").unwrap();
+        let track = CameraTrack::from_config(&CameraConfig::default(), &animation_config);
+
+        assert!(matches!(track, Err(Error::Scene(_))));
+    }
+}
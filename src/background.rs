@@ -0,0 +1,239 @@
+use std::{f64::consts::PI, fmt, fs::File, io::BufReader, path::Path};
+
+use image::{codecs::hdr::HdrDecoder, GenericImageView};
+use rand::Rng;
+
+use crate::{
+    error::{Error, Result},
+    util::RandomDouble,
+    vec3::Vec3,
+};
+
+/// What a ray sees when it leaves the world without hitting anything.
+#[derive(Clone, Debug)]
+pub enum Background {
+    Constant(Vec3),
+    /// Blends from `bottom` when looking straight down to `top` when looking up.
+    Gradient {
+        bottom: Vec3,
+        top: Vec3,
+    },
+    Environment(EnvironmentMap),
+}
+
+impl Background {
+    pub fn constant(color: Vec3) -> Self {
+        Self::Constant(color)
+    }
+
+    pub fn gradient(bottom: Vec3, top: Vec3) -> Self {
+        Self::Gradient { bottom, top }
+    }
+
+    pub fn environment(map: EnvironmentMap) -> Self {
+        Self::Environment(map)
+    }
+
+    /// The environment map, if there's one worth sampling as a light.
+    pub fn environment_map(&self) -> Option<&EnvironmentMap> {
+        match self {
+            Background::Environment(map) if map.can_sample() => Some(map),
+            _ => None,
+        }
+    }
+
+    pub fn value(&self, direction: &Vec3) -> Vec3 {
+        match self {
+            Background::Constant(color) => color.clone(),
+            Background::Gradient { bottom, top } => {
+                let t = 0.5 * (direction.unit().y() + 1.0);
+                (1.0 - t) * bottom + t * top
+            }
+            Background::Environment(map) => map.value(direction),
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::constant(Vec3::default())
+    }
+}
+
+/// An equirectangular image surrounding the whole world, with the center of the
+/// image in the -Z direction and +Y at the top.
+#[derive(Clone)]
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+    /// Around +Y, in degrees.
+    rotation: f64,
+    intensity: f64,
+    /// Cumulative weight of each row, ending with the total.
+    row_cdf: Vec<f64>,
+    /// Cumulative weight of each pixel within its row, `width + 1` entries per row.
+    column_cdfs: Vec<f64>,
+}
+
+impl EnvironmentMap {
+    /// Radiance HDR files keep their full range, other formats are read as 0-1.
+    pub fn open<P: AsRef<Path>>(path: P, rotation: f64, intensity: f64) -> Result<Self> {
+        let path = path.as_ref();
+        let is_hdr = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("hdr"));
+
+        let (width, height, pixels): (usize, usize, Vec<Vec3>) = if is_hdr {
+            let file = File::open(path).map_err(|e| Error::io(path, e))?;
+            let decoder =
+                HdrDecoder::new(BufReader::new(file)).map_err(|e| Error::image(path, e))?;
+            let metadata = decoder.metadata();
+            let pixels = decoder
+                .read_image_hdr()
+                .map_err(|e| Error::image(path, e))?
+                .into_iter()
+                .map(|pixel| Vec3::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64))
+                .collect();
+
+            (metadata.width as usize, metadata.height as usize, pixels)
+        } else {
+            let image = image::open(path).map_err(|e| Error::image(path, e))?;
+            let color_scale = 1.0 / 255.0;
+            let pixels = image
+                .to_rgb8()
+                .pixels()
+                .map(|pixel| {
+                    color_scale * Vec3::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64)
+                })
+                .collect();
+
+            (image.width() as usize, image.height() as usize, pixels)
+        };
+
+        let (row_cdf, column_cdfs) = build_cdfs(width, height, &pixels);
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+            rotation,
+            intensity,
+            row_cdf,
+            column_cdfs,
+        })
+    }
+
+    pub fn value(&self, direction: &Vec3) -> Vec3 {
+        let (u, v) = self.direction_to_uv(direction);
+
+        let i = ((u * self.width as f64) as usize).min(self.width - 1);
+        let j = ((v * self.height as f64) as usize).min(self.height - 1);
+
+        self.intensity * &self.pixels[j * self.width + i]
+    }
+
+    fn can_sample(&self) -> bool {
+        self.row_cdf.last().is_some_and(|&total| total > 0.0)
+    }
+
+    /// Picks a direction with a density proportional to the map's luminance,
+    /// so bright spots like the sun are found far more often than by chance.
+    pub fn sample<T: Rng>(&self, rng: &mut T) -> Vec3 {
+        let j = sample_cdf(&self.row_cdf, rng.random_double());
+        let row = &self.column_cdfs[j * (self.width + 1)..(j + 1) * (self.width + 1)];
+        let i = sample_cdf(row, rng.random_double());
+
+        let u = (i as f64 + rng.random_double()) / self.width as f64;
+        let v = (j as f64 + rng.random_double()) / self.height as f64;
+
+        self.uv_to_direction(u, v)
+    }
+
+    /// Solid angle density of `sample` picking `direction`.
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+
+        let i = ((u * self.width as f64) as usize).min(self.width - 1);
+        let j = ((v * self.height as f64) as usize).min(self.height - 1);
+
+        let total = self.row_cdf[self.height];
+        let offset = j * (self.width + 1) + i;
+        let weight = self.column_cdfs[offset + 1] - self.column_cdfs[offset];
+
+        // Each pixel is sampled uniformly in uv, which covers 2π² sin(θ) per unit area
+        let pdf_uv = weight / total * (self.width * self.height) as f64;
+        pdf_uv / (2.0 * PI * PI * sin_theta)
+    }
+
+    /// `u` runs left to right and `v` top to bottom across the image.
+    fn direction_to_uv(&self, direction: &Vec3) -> (f64, f64) {
+        let direction = direction.unit();
+
+        let phi = direction.x().atan2(-direction.z()) - self.rotation.to_radians();
+        let theta = direction.y().clamp(-1.0, 1.0).acos();
+
+        let u = (phi / (2.0 * PI) + 0.5).rem_euclid(1.0);
+        let v = theta / PI;
+
+        (u, v)
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vec3 {
+        let phi = (u - 0.5) * 2.0 * PI + self.rotation.to_radians();
+        let theta = v * PI;
+
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let (sin_phi, cos_phi) = phi.sin_cos();
+
+        Vec3::new(sin_theta * sin_phi, cos_theta, -sin_theta * cos_phi)
+    }
+}
+
+/// Weighs every pixel by its luminance and the solid angle it covers, which
+/// shrinks towards the poles.
+fn build_cdfs(width: usize, height: usize, pixels: &[Vec3]) -> (Vec<f64>, Vec<f64>) {
+    let mut row_cdf = Vec::with_capacity(height + 1);
+    let mut column_cdfs = Vec::with_capacity(height * (width + 1));
+
+    row_cdf.push(0.0);
+    for (j, row) in pixels.chunks(width).enumerate() {
+        let sin_theta = ((j as f64 + 0.5) / height as f64 * PI).sin();
+
+        let mut sum = 0.0;
+        column_cdfs.push(sum);
+        for pixel in row {
+            let luminance = 0.2126 * pixel.r() + 0.7152 * pixel.g() + 0.0722 * pixel.b();
+            sum += luminance.max(0.0) * sin_theta;
+            column_cdfs.push(sum);
+        }
+
+        let previous = row_cdf[row_cdf.len() - 1];
+        row_cdf.push(previous + sum);
+    }
+
+    (row_cdf, column_cdfs)
+}
+
+/// Index of the bucket `x` (in 0-1) falls into, skipping empty ones.
+fn sample_cdf(cdf: &[f64], x: f64) -> usize {
+    let target = x * cdf[cdf.len() - 1];
+    let upper = cdf[1..].partition_point(|&value| value <= target);
+    upper.min(cdf.len() - 2)
+}
+
+impl fmt::Debug for EnvironmentMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EnvironmentMap")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("rotation", &self.rotation)
+            .field("intensity", &self.intensity)
+            .finish()
+    }
+}
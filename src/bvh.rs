@@ -1,92 +1,356 @@
-use std::cmp::Ordering;
+use std::mem;
 
-use rand::Rng;
+use rand::{rngs::SmallRng, Rng};
+use serde_derive::{Deserialize, Serialize};
 
 use crate::{
     aabb::AABB,
     hittable::{Hit, Hittable, Shape},
     ray::Ray,
+    vec3::Vec3,
 };
 
+/// Nodes with at most this many shapes become leaves when splitting by SAH.
+const MAX_LEAF_SIZE: usize = 4;
+
+/// Number of buckets the centroid range is divided into when searching for the
+/// cheapest SAH split.
+const SAH_BINS: usize = 12;
+
+/// Relative cost of visiting an interior node versus testing one shape.
+const TRAVERSAL_COST: f64 = 0.125;
+
+/// Past this depth the SAH builder falls back to halving by count, which keeps
+/// the tree shallow enough for the fixed size traversal stack.
+const MAX_SAH_DEPTH: usize = 64;
+
+const MAX_STACK_SIZE: usize = 128;
+
+/// Refitting keeps the tree's shape, which gets worse as shapes move away from
+/// where it was built. Past this many times the SAH cost it was built with, it's
+/// rebuilt instead.
+const REBUILD_COST_RATIO: f64 = 1.5;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitMethod {
+    /// Binned surface area heuristic over all three axes.
+    #[default]
+    Sah,
+    /// Halves the shapes by count along a random axis.
+    Median,
+}
+
 #[derive(Debug)]
 pub struct BoundingVolumeHierarchy {
     shapes: Vec<Shape>,
     nodes: Vec<BVHMember>,
+    /// SAH cost when last built, to compare refits against.
+    built_cost: f64,
+    /// Whether any of the shapes are participating media, without which shadow
+    /// rays don't need to look for them.
+    has_media: bool,
 }
 
-impl<'a> BoundingVolumeHierarchy {
+impl BoundingVolumeHierarchy {
     pub fn new<T: Rng>(
         rng: &mut T,
         shapes: Vec<Shape>,
+        split_method: SplitMethod,
         time_initial: f64,
         time_final: f64,
     ) -> BoundingVolumeHierarchy {
-        let mut shapes = shapes;
-        let nodes = build_tree(rng, &mut shapes, time_initial, time_final);
-        BoundingVolumeHierarchy { shapes, nodes }
+        let has_media = shapes.iter().any(Shape::has_media);
+        let (shapes, nodes) = build_tree(rng, shapes, split_method, time_initial, time_final);
+        let mut bvh = BoundingVolumeHierarchy {
+            shapes,
+            nodes,
+            built_cost: 0.0,
+            has_media,
+        };
+        bvh.built_cost = bvh.cost();
+
+        bvh
+    }
+
+    /// Builds the tree again over the same shapes, bounding them over a new time
+    /// interval.
+    pub fn rebuild<T: Rng>(
+        &mut self,
+        rng: &mut T,
+        split_method: SplitMethod,
+        time_initial: f64,
+        time_final: f64,
+    ) {
+        let shapes = mem::take(&mut self.shapes);
+        let (shapes, nodes) = build_tree(rng, shapes, split_method, time_initial, time_final);
+
+        self.shapes = shapes;
+        self.nodes = nodes;
+        self.built_cost = self.cost();
+    }
+
+    /// Recomputes every node's bounds from its shapes over a new time interval,
+    /// keeping the tree as it is. Much cheaper than a rebuild, but only as good as
+    /// the old tree is for where the shapes are now.
+    pub fn refit(&mut self, time_initial: f64, time_final: f64) {
+        // Children always come before their parents, so one pass fixes every node
+        for index in 0..self.nodes.len() {
+            let refitted = match &self.nodes[index] {
+                BVHMember::Leaf { offset, count, .. } => self.shapes[*offset..*offset + *count]
+                    .iter()
+                    .map(|shape| {
+                        let shape: &dyn Hittable<'_, SmallRng> = shape;
+                        shape
+                            .bounding_box(time_initial, time_final)
+                            .expect("should exist")
+                    })
+                    .reduce(|aabb, other| aabb.surrounding_box(&other))
+                    .expect("leaves are never empty"),
+                BVHMember::Node { left, right, .. } => self.nodes[*left]
+                    .bounds()
+                    .surrounding_box(self.nodes[*right].bounds()),
+            };
+
+            match &mut self.nodes[index] {
+                BVHMember::Leaf { bounds, .. } | BVHMember::Node { bounds, .. } => {
+                    *bounds = refitted
+                }
+            }
+        }
+    }
+
+    /// Refits for a new time interval, or rebuilds if that leaves the tree much
+    /// worse than when it was built. Returns whether it was rebuilt.
+    pub fn update<T: Rng>(
+        &mut self,
+        rng: &mut T,
+        split_method: SplitMethod,
+        time_initial: f64,
+        time_final: f64,
+    ) -> bool {
+        self.refit(time_initial, time_final);
+
+        if self.cost() > self.built_cost * REBUILD_COST_RATIO {
+            self.rebuild(rng, split_method, time_initial, time_final);
+            return true;
+        }
+
+        false
+    }
+
+    /// Expected cost of tracing a ray through the tree by the surface area
+    /// heuristic, relative to testing a single shape.
+    pub fn cost(&self) -> f64 {
+        let root_area = match self.root() {
+            Some(root) => root.bounds().surface_area(),
+            None => return 0.0,
+        };
+        if root_area <= 0.0 {
+            return 0.0;
+        }
+
+        self.nodes
+            .iter()
+            .map(|node| {
+                let work = match node {
+                    BVHMember::Node { .. } => TRAVERSAL_COST,
+                    BVHMember::Leaf { count, .. } => *count as f64,
+                };
+
+                work * node.bounds().surface_area() / root_area
+            })
+            .sum()
     }
 
     pub fn shapes(&self) -> &Vec<Shape> {
         &self.shapes
     }
 
-    fn root(&self) -> &BVHMember {
-        &self.nodes[self.nodes.len() - 1]
+    pub fn has_media(&self) -> bool {
+        self.has_media
+    }
+
+    pub fn nodes(&self) -> &[BVHMember] {
+        &self.nodes
+    }
+
+    /// Bounds of every shape over the time the tree was built for.
+    pub fn bounds(&self) -> Option<&AABB> {
+        self.root().map(BVHMember::bounds)
+    }
+
+    /// Number of levels from the root down to the deepest leaf.
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push((self.nodes.len() - 1, 1));
+        }
+
+        while let Some((index, level)) = stack.pop() {
+            depth = depth.max(level);
+            if let BVHMember::Node { left, right, .. } = &self.nodes[index] {
+                stack.push((*left, level + 1));
+                stack.push((*right, level + 1));
+            }
+        }
+
+        depth
+    }
+
+    fn root(&self) -> Option<&BVHMember> {
+        // NOTE(Matt): The root is technically at the "end" of the list
+        self.nodes.last()
     }
 
-    fn indexed_hit<T: Rng>(
+    /// The closest of the hits `hit` finds on each shape along `ray`, given the
+    /// distance to the closest one so far.
+    fn nearest_hit<'a, T: Rng, F>(
         &'a self,
-        member: &BVHMember,
         ray: &Ray,
         t_min: f64,
         t_max: f64,
-    ) -> Option<Hit<'a, T>> {
-        let (bounds, left, right) = match member {
-            BVHMember::Leaf(index) => {
-                let shape = &self.shapes[*index];
-                return shape.hit(ray, t_min, t_max);
+        mut hit: F,
+    ) -> Option<Hit<'a, T>>
+    where
+        F: FnMut(&'a Shape, f64) -> Option<Hit<'a, T>>,
+    {
+        let root_entry = self.root()?.bounds().intersect(ray, t_min, t_max)?;
+
+        // Each level leaves at most one sibling behind, so the depth cap on the
+        // build bounds how deep this can get
+        let mut stack = [(0, 0.0); MAX_STACK_SIZE];
+        stack[0] = (self.nodes.len() - 1, root_entry);
+        let mut stack_size = 1;
+
+        let mut min_distance = t_max;
+        let mut nearest_hit = None;
+
+        while stack_size > 0 {
+            stack_size -= 1;
+            let (index, entry) = stack[stack_size];
+
+            // Something closer was found since this node was queued
+            if entry > min_distance {
+                continue;
             }
-            BVHMember::Node {
-                bounds,
-                left,
-                right,
-            } => {
-                let left = left.and_then(|index| self.nodes.get(index));
-                let right = right.and_then(|index| self.nodes.get(index));
 
-                (bounds, left, right)
+            match &self.nodes[index] {
+                BVHMember::Leaf { offset, count, .. } => {
+                    for shape in self.shapes[*offset..*offset + *count].iter() {
+                        if let Some(hit) = hit(shape, min_distance) {
+                            min_distance = hit.t();
+                            nearest_hit = Some(hit);
+                        }
+                    }
+                }
+                BVHMember::Node { left, right, .. } => {
+                    let left_entry = self.nodes[*left]
+                        .bounds()
+                        .intersect(ray, t_min, min_distance);
+                    let right_entry =
+                        self.nodes[*right]
+                            .bounds()
+                            .intersect(ray, t_min, min_distance);
+
+                    // Push the far child first so the near one is visited next
+                    let mut push = |node| {
+                        stack[stack_size] = node;
+                        stack_size += 1;
+                    };
+
+                    match (left_entry, right_entry) {
+                        (Some(left_entry), Some(right_entry)) => {
+                            if left_entry < right_entry {
+                                push((*right, right_entry));
+                                push((*left, left_entry));
+                            } else {
+                                push((*left, left_entry));
+                                push((*right, right_entry));
+                            }
+                        }
+                        (Some(left_entry), None) => push((*left, left_entry)),
+                        (None, Some(right_entry)) => push((*right, right_entry)),
+                        (None, None) => {}
+                    }
+                }
             }
-        };
+        }
 
-        if !bounds.hit(ray, t_min, t_max) {
-            return None;
+        nearest_hit
+    }
+}
+
+impl<'a, T: Rng> Hittable<'a, T> for BoundingVolumeHierarchy {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        self.nearest_hit(ray, t_min, t_max, |shape, t_max| {
+            shape.hit(ray, t_min, t_max, rng)
+        })
+    }
+
+    fn surface_hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        if !self.has_media {
+            return self.hit(ray, t_min, t_max, rng);
         }
 
-        let left_hit = left.and_then(|member| self.indexed_hit(member, ray, t_min, t_max));
-        let right_hit = right.and_then(|member| self.indexed_hit(member, ray, t_min, t_max));
+        self.nearest_hit(ray, t_min, t_max, |shape, t_max| {
+            shape.surface_hit(ray, t_min, t_max, rng)
+        })
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> f64 {
+        if !self.has_media {
+            return 1.0;
+        }
+
+        match self.root() {
+            Some(root) if root.bounds().intersect(ray, t_min, t_max).is_some() => {}
+            _ => return 1.0,
+        }
 
-        match (left_hit, right_hit) {
-            (Some(left_hit), Some(right_hit)) => {
-                if left_hit.t() < right_hit.t() {
-                    Some(left_hit)
-                } else {
-                    Some(right_hit)
+        // Every medium along the way dims the light, so there's no stopping at the
+        // first one
+        let mut stack = [0; MAX_STACK_SIZE];
+        stack[0] = self.nodes.len() - 1;
+        let mut stack_size = 1;
+
+        let mut transmittance = 1.0;
+        while stack_size > 0 {
+            stack_size -= 1;
+            match &self.nodes[stack[stack_size]] {
+                BVHMember::Leaf { offset, count, .. } => {
+                    for shape in self.shapes[*offset..*offset + *count].iter() {
+                        if shape.has_media() {
+                            let shape: &dyn Hittable<'_, T> = shape;
+                            transmittance *= shape.transmittance(ray, t_min, t_max, rng);
+                        }
+                    }
+
+                    if transmittance <= 0.0 {
+                        return 0.0;
+                    }
+                }
+                BVHMember::Node { left, right, .. } => {
+                    for &child in &[*left, *right] {
+                        if self.nodes[child]
+                            .bounds()
+                            .intersect(ray, t_min, t_max)
+                            .is_some()
+                        {
+                            stack[stack_size] = child;
+                            stack_size += 1;
+                        }
+                    }
                 }
             }
-            (Some(left_hit), None) => Some(left_hit),
-            (None, Some(right_hit)) => Some(right_hit),
-            (None, None) => None,
         }
-    }
-}
 
-impl<'a, T: Rng> Hittable<'a, T> for BoundingVolumeHierarchy {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_, T>> {
-        self.indexed_hit(self.root(), ray, t_min, t_max)
+        transmittance
     }
 
     fn bounding_box(&self, _time_start: f64, _time_end: f64) -> Option<AABB> {
-        self.root().bounds().cloned()
+        self.bounds().cloned()
     }
 }
 
@@ -94,144 +358,393 @@ impl<'a, T: Rng> Hittable<'a, T> for BoundingVolumeHierarchy {
 pub enum BVHMember {
     Node {
         bounds: AABB,
-        left: Option<usize>,
-        right: Option<usize>,
+        left: usize,
+        right: usize,
+    },
+    /// A contiguous run of `count` shapes starting at `offset`.
+    Leaf {
+        bounds: AABB,
+        offset: usize,
+        count: usize,
     },
-    Leaf(usize),
 }
 
 impl BVHMember {
-    pub fn bounds(&self) -> Option<&AABB> {
+    pub fn bounds(&self) -> &AABB {
         match self {
-            BVHMember::Node { bounds, .. } => Some(bounds),
-            BVHMember::Leaf(_) => None,
+            BVHMember::Node { bounds, .. } => bounds,
+            BVHMember::Leaf { bounds, .. } => bounds,
         }
     }
 }
 
+/// A shape's place in the shape list along with the bounds the split decisions
+/// are made from, so they're only computed once per build.
+struct BuildItem {
+    index: usize,
+    bounds: AABB,
+    centroid: Vec3,
+}
+
 fn build_tree<T: Rng>(
     rng: &mut T,
-    shapes: &mut [Shape],
+    shapes: Vec<Shape>,
+    split_method: SplitMethod,
     time_initial: f64,
     time_final: f64,
-) -> Vec<BVHMember> {
+) -> (Vec<Shape>, Vec<BVHMember>) {
+    let mut items = shapes
+        .iter()
+        .enumerate()
+        .map(|(index, shape)| {
+            let shape: &dyn Hittable<'_, T> = shape;
+            let bounds = shape
+                .bounding_box(time_initial, time_final)
+                .expect("should exist");
+            let centroid = 0.5 * (bounds.min() + bounds.max());
+
+            BuildItem {
+                index,
+                bounds,
+                centroid,
+            }
+        })
+        .collect::<Vec<_>>();
+
     let mut nodes = Vec::with_capacity(shapes.len() * 2);
 
-    let root = build_tree_internal(
-        rng,
-        shapes,
-        &mut nodes,
-        0,
-        shapes.len(),
-        time_initial,
-        time_final,
-    );
-
-    // NOTE(Matt): The root is technically at the "end" of the list
-    nodes.push(root);
+    if !items.is_empty() {
+        let root = match split_method {
+            SplitMethod::Sah => build_sah(&mut items, &mut nodes, 0, 0),
+            SplitMethod::Median => build_median(rng, &mut items, &mut nodes, 0),
+        };
+
+        // NOTE(Matt): The root is technically at the "end" of the list
+        nodes.push(root);
+    }
     nodes.shrink_to_fit();
 
-    nodes
+    // Reorder the shapes to match the leaves, which reference contiguous ranges
+    let mut shapes = shapes.into_iter().map(Some).collect::<Vec<_>>();
+    let shapes = items
+        .iter()
+        .map(|item| shapes[item.index].take().expect("each shape appears once"))
+        .collect();
+
+    (shapes, nodes)
 }
 
-fn build_tree_internal<T: Rng>(
-    rng: &mut T,
-    shapes: &mut [Shape],
+fn build_sah(
+    items: &mut [BuildItem],
     nodes: &mut Vec<BVHMember>,
     offset: usize,
-    n: usize,
-    time_initial: f64,
-    time_final: f64,
+    depth: usize,
 ) -> BVHMember {
-    let axis = rng.gen_range(0, 3);
-    shapes.sort_by(|a, b| {
-        let a: &dyn Hittable<'_, T> = a;
-        let b: &dyn Hittable<'_, T> = b;
-
-        let left = a
-            .bounding_box(time_initial, time_final)
-            .expect("should exist");
-        let right = b
-            .bounding_box(time_initial, time_final)
-            .expect("should exist");
-
-        let left_axis = left.min()[axis];
-        let right_axis = right.min()[axis];
-
-        if left_axis - right_axis < 0.0 {
-            Ordering::Less
-        } else {
-            Ordering::Greater
-        }
-    });
-
-    let bounding_box = |left: &dyn Hittable<'_, T>, right: &dyn Hittable<'_, T>| {
-        let left = left
-            .bounding_box(time_initial, time_final)
-            .expect("should exist");
-        let right = right
-            .bounding_box(time_initial, time_final)
-            .expect("should exist");
-
-        left.surrounding_box(&right)
+    let bounds = surrounding_box(items.iter().map(|item| &item.bounds));
+    let count = items.len();
+
+    if count <= MAX_LEAF_SIZE {
+        return BVHMember::Leaf {
+            bounds,
+            offset,
+            count,
+        };
+    }
+
+    let centroid_bounds = {
+        let first = AABB::new(items[0].centroid.clone(), items[0].centroid.clone());
+        items.iter().skip(1).fold(first, |aabb, item| {
+            aabb.surrounding_box(&AABB::new(item.centroid.clone(), item.centroid.clone()))
+        })
     };
 
-    if n == 1 {
-        BVHMember::Leaf(offset)
-    } else if n == 2 {
-        let left_hittable = &shapes[0];
-        let right_hittable = &shapes[1];
+    let split = if depth < MAX_SAH_DEPTH {
+        best_sah_split(items, &bounds, &centroid_bounds)
+    } else {
+        None
+    };
+
+    let halving_point = match split {
+        Some((axis, bin)) => {
+            let (min, extent) = axis_extent(&centroid_bounds, axis);
+            partition(items, |item| {
+                centroid_bin(&item.centroid, axis, min, extent) <= bin
+            })
+        }
+        // Either every centroid is in the same spot so no plane can separate them,
+        // or the tree is already too deep
+        None => count / 2,
+    };
 
-        let bounds = bounding_box(left_hittable, right_hittable);
+    let (left_items, right_items) = items.split_at_mut(halving_point);
 
-        let left_leaf = BVHMember::Leaf(offset);
-        let right_leaf = BVHMember::Leaf(offset + 1);
+    let left = build_sah(left_items, nodes, offset, depth + 1);
+    let left_index = nodes.len();
+    nodes.push(left);
 
-        let left_index = nodes.len();
-        nodes.push(left_leaf);
+    let right = build_sah(right_items, nodes, offset + halving_point, depth + 1);
+    let right_index = nodes.len();
+    nodes.push(right);
 
-        let right_index = nodes.len();
-        nodes.push(right_leaf);
+    BVHMember::Node {
+        bounds,
+        left: left_index,
+        right: right_index,
+    }
+}
 
-        BVHMember::Node {
-            left: Some(left_index),
-            right: Some(right_index),
-            bounds,
+/// Finds the axis and bucket to split after, or `None` if the centroids can't be
+/// separated along any axis.
+fn best_sah_split(
+    items: &[BuildItem],
+    bounds: &AABB,
+    centroid_bounds: &AABB,
+) -> Option<(usize, usize)> {
+    let parent_area = bounds.surface_area();
+    let mut best: Option<(f64, usize, usize)> = None;
+
+    for axis in 0..3 {
+        let (min, extent) = axis_extent(centroid_bounds, axis);
+        if extent <= 0.0 {
+            continue;
         }
-    } else {
-        let halving_point = n / 2;
 
-        let left = build_tree_internal(
-            rng,
-            &mut shapes[..halving_point],
-            nodes,
-            offset,
-            halving_point,
-            time_initial,
-            time_final,
-        );
-        let left_index = nodes.len();
-        nodes.push(left);
-
-        let right = build_tree_internal(
-            rng,
-            &mut shapes[halving_point..],
-            nodes,
-            offset + halving_point,
-            n - halving_point,
-            time_initial,
-            time_final,
-        );
-        let right_index = nodes.len();
-        nodes.push(right);
-
-        let shapes = &shapes as &dyn Hittable<'_, T>;
-        let bounds = shapes.bounding_box(time_initial, time_final).expect("");
-
-        BVHMember::Node {
-            left: Some(left_index),
-            right: Some(right_index),
+        let mut bins: Vec<(usize, Option<AABB>)> = (0..SAH_BINS).map(|_| (0, None)).collect();
+        for item in items {
+            let (count, aabb) = &mut bins[centroid_bin(&item.centroid, axis, min, extent)];
+
+            *count += 1;
+            *aabb = Some(match aabb {
+                Some(aabb) => aabb.surrounding_box(&item.bounds),
+                None => item.bounds.clone(),
+            });
+        }
+
+        // Sweep from the right first so each split's right side cost is a lookup
+        let mut right_costs = [0.0; SAH_BINS];
+        let mut right_count = 0;
+        let mut right_bounds: Option<AABB> = None;
+        for bin in (1..SAH_BINS).rev() {
+            let (count, aabb) = &bins[bin];
+            right_count += count;
+            right_bounds = merge(right_bounds, aabb.as_ref());
+            right_costs[bin - 1] = area(&right_bounds) * right_count as f64;
+        }
+
+        let mut left_count = 0;
+        let mut left_bounds: Option<AABB> = None;
+        for (bin, (count, aabb)) in bins.iter().enumerate().take(SAH_BINS - 1) {
+            left_count += count;
+            left_bounds = merge(left_bounds, aabb.as_ref());
+
+            if left_count == 0 || left_count == items.len() {
+                continue;
+            }
+
+            let cost = TRAVERSAL_COST
+                + (area(&left_bounds) * left_count as f64 + right_costs[bin]) / parent_area;
+
+            if best.map_or(true, |(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, bin));
+            }
+        }
+    }
+
+    best.map(|(_, axis, bin)| (axis, bin))
+}
+
+fn build_median<T: Rng>(
+    rng: &mut T,
+    items: &mut [BuildItem],
+    nodes: &mut Vec<BVHMember>,
+    offset: usize,
+) -> BVHMember {
+    let bounds = surrounding_box(items.iter().map(|item| &item.bounds));
+    let n = items.len();
+
+    if n == 1 {
+        return BVHMember::Leaf {
             bounds,
+            offset,
+            count: 1,
+        };
+    }
+
+    let axis = rng.gen_range(0, 3);
+    items.sort_by(|a, b| a.bounds.min()[axis].total_cmp(&b.bounds.min()[axis]));
+
+    let halving_point = n / 2;
+    let (left_items, right_items) = items.split_at_mut(halving_point);
+
+    let left = build_median(rng, left_items, nodes, offset);
+    let left_index = nodes.len();
+    nodes.push(left);
+
+    let right = build_median(rng, right_items, nodes, offset + halving_point);
+    let right_index = nodes.len();
+    nodes.push(right);
+
+    BVHMember::Node {
+        bounds,
+        left: left_index,
+        right: right_index,
+    }
+}
+
+fn axis_extent(centroid_bounds: &AABB, axis: usize) -> (f64, f64) {
+    let min = centroid_bounds.min()[axis];
+    let max = centroid_bounds.max()[axis];
+
+    (min, max - min)
+}
+
+fn centroid_bin(centroid: &Vec3, axis: usize, min: f64, extent: f64) -> usize {
+    let bin = ((centroid[axis] - min) / extent * SAH_BINS as f64) as usize;
+    bin.min(SAH_BINS - 1)
+}
+
+/// Moves every item matching `predicate` to the front, returning how many there are.
+fn partition<F: Fn(&BuildItem) -> bool>(items: &mut [BuildItem], predicate: F) -> usize {
+    let mut boundary = 0;
+    for i in 0..items.len() {
+        if predicate(&items[i]) {
+            items.swap(i, boundary);
+            boundary += 1;
         }
     }
+
+    boundary
+}
+
+fn surrounding_box<'a, I: Iterator<Item = &'a AABB>>(mut boxes: I) -> AABB {
+    let first = boxes.next().expect("nodes are never empty").clone();
+    boxes.fold(first, |aabb, other| aabb.surrounding_box(other))
+}
+
+fn merge(aabb: Option<AABB>, other: Option<&AABB>) -> Option<AABB> {
+    match (aabb, other) {
+        (Some(aabb), Some(other)) => Some(aabb.surrounding_box(other)),
+        (Some(aabb), None) => Some(aabb),
+        (None, other) => other.cloned(),
+    }
+}
+
+fn area(aabb: &Option<AABB>) -> f64 {
+    aabb.as_ref().map_or(0.0, AABB::surface_area)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::material::Material;
+
+    /// A row of spheres along x, each moving by `offset(i)` between times 0 and 1.
+    fn moving_row<F: Fn(f64) -> Vec3>(offset: F) -> Vec<Shape> {
+        (0..32)
+            .map(|i| {
+                let x = i as f64;
+                let to = Vec3::new(x, 0.0, 0.0) + offset(x);
+                let material = Material::lambertian(0.5, 0.5, 0.5);
+                Shape::moving_sphere(x, 0.0, 0.0, to.x(), to.y(), to.z(), 0.4, material, 0.0, 1.0)
+            })
+            .collect()
+    }
+
+    fn bvh(shapes: Vec<Shape>) -> BoundingVolumeHierarchy {
+        let mut rng = SmallRng::seed_from_u64(0);
+        BoundingVolumeHierarchy::new(&mut rng, shapes, SplitMethod::Sah, 0.0, 0.0)
+    }
+
+    fn contains(outer: &AABB, inner: &AABB) -> bool {
+        (0..3).all(|axis| {
+            outer.min()[axis] <= inner.min()[axis] && outer.max()[axis] >= inner.max()[axis]
+        })
+    }
+
+    #[test]
+    fn refitted_nodes_bound_their_moved_shapes() {
+        let mut bvh = bvh(moving_row(|x| Vec3::new(0.0, x * x / 8.0, 3.0 - x)));
+        bvh.refit(1.0, 1.0);
+
+        for node in bvh.nodes() {
+            match node {
+                BVHMember::Leaf {
+                    bounds,
+                    offset,
+                    count,
+                } => {
+                    for shape in &bvh.shapes()[*offset..*offset + *count] {
+                        let shape: &dyn Hittable<'_, SmallRng> = shape;
+                        assert!(contains(bounds, &shape.bounding_box(1.0, 1.0).unwrap()));
+                    }
+                }
+                BVHMember::Node {
+                    bounds,
+                    left,
+                    right,
+                } => {
+                    assert!(contains(bounds, bvh.nodes()[*left].bounds()));
+                    assert!(contains(bounds, bvh.nodes()[*right].bounds()));
+                }
+            }
+        }
+
+        let root = bvh.bounds().unwrap();
+        assert!((root.max().y() - (31.0 * 31.0 / 8.0 + 0.4)).abs() < 1e-9);
+        assert!((root.min().z() - (3.0 - 31.0 - 0.4)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn update_refits_while_the_tree_is_still_good() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut bvh = bvh(moving_row(|_| Vec3::new(0.0, 1.0, 0.0)));
+        let built_cost = bvh.cost();
+
+        assert!(!bvh.update(&mut rng, SplitMethod::Sah, 1.0, 1.0));
+        assert!((bvh.cost() - built_cost).abs() < 1e-9);
+        assert!((bvh.bounds().unwrap().min().y() - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn update_rebuilds_once_refitting_costs_too_much() {
+        // Shuffling the row leaves every node spanning most of it
+        let shuffle = |x: f64| Vec3::new((x * 13.0) % 32.0 - x, 0.0, 0.0);
+
+        let mut refitted = bvh(moving_row(shuffle));
+        let built_cost = refitted.cost();
+        refitted.refit(1.0, 1.0);
+        assert!(refitted.cost() > REBUILD_COST_RATIO * built_cost);
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut updated = bvh(moving_row(shuffle));
+        assert!(updated.update(&mut rng, SplitMethod::Sah, 1.0, 1.0));
+        assert!(updated.cost() <= REBUILD_COST_RATIO * built_cost);
+        assert!(updated.cost() < refitted.cost());
+    }
+
+    #[test]
+    fn median_split_copes_with_ties() {
+        // Stacked rects share their minimum on every axis but z
+        let shapes = (0..64)
+            .map(|i| {
+                let material = Material::lambertian(0.5, 0.5, 0.5);
+                Shape::xy_rect(0.0, 1.0, 0.0, 1.0, (i % 4) as f64, material)
+            })
+            .collect();
+        let mut rng = SmallRng::seed_from_u64(0);
+        let bvh = BoundingVolumeHierarchy::new(&mut rng, shapes, SplitMethod::Median, 0.0, 0.0);
+
+        let leaf_shapes: usize = bvh
+            .nodes()
+            .iter()
+            .map(|node| match node {
+                BVHMember::Leaf { count, .. } => *count,
+                BVHMember::Node { .. } => 0,
+            })
+            .sum();
+        assert_eq!(leaf_shapes, 64);
+        assert_eq!(bvh.depth(), 7);
+    }
 }
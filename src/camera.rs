@@ -1,10 +1,38 @@
 use std::f64::consts::PI;
 
-use rand::Rng;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde_derive::{Deserialize, Serialize};
 
-use crate::{ray::Ray, util::RandomDouble, vec3::Vec3};
+use crate::{
+    hittable::{Hit, Hittable},
+    ray::Ray,
+    scene::Scene,
+    util::RandomDouble,
+    vec3::Vec3,
+};
 
+/// How the camera maps points on screen to directions in the world.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Projection {
+    /// A thin lens, with depth of field when the aperture is open.
+    #[default]
+    Perspective,
+    /// Parallel rays, framing what a perspective camera would see at the focus
+    /// distance.
+    Orthographic,
+    /// Equidistant, so the angle from the view direction grows linearly towards
+    /// the edges and the field of view covers the height of the image. Nothing is
+    /// seen past 180 degrees from the view direction.
+    Fisheye,
+    /// The whole sphere around the camera, 360 degrees across and 180 degrees
+    /// top to bottom, ignoring the field of view. Made for 2:1 images.
+    Equirectangular,
+}
+
+#[derive(Clone, Debug)]
 pub struct Camera {
+    projection: Projection,
     origin: Vec3,
     lower_left_corner: Vec3,
     horizontal: Vec3,
@@ -12,6 +40,9 @@ pub struct Camera {
     u: Vec3,
     v: Vec3,
     w: Vec3,
+    /// In radians.
+    vertical_fov: f64,
+    aspect: f64,
     lens_radius: f64,
     time_start: f64,
     time_end: f64,
@@ -19,6 +50,7 @@ pub struct Camera {
 
 impl Camera {
     pub fn new(
+        projection: Projection,
         look_from: Vec3,
         look_at: Vec3,
         v_up: Vec3,
@@ -41,14 +73,17 @@ impl Camera {
         let v = w.cross(&u);
 
         let origin = look_from;
-        let lower_left_corner = &origin
-            - (half_width * focus_distance * &u)
-            - (half_height * focus_distance * &v)
-            - focus_distance * &w;
+        let mut lower_left_corner =
+            &origin - (half_width * focus_distance * &u) - (half_height * focus_distance * &v);
+        // Orthographic rays start from the image itself rather than passing through it
+        if projection != Projection::Orthographic {
+            lower_left_corner -= focus_distance * &w;
+        }
         let horizontal = 2.0 * half_width * focus_distance * &u;
         let vertical = 2.0 * half_height * focus_distance * &v;
 
         Camera {
+            projection,
             origin,
             lower_left_corner,
             horizontal,
@@ -56,22 +91,192 @@ impl Camera {
             u,
             v,
             w,
+            vertical_fov: theta,
+            aspect,
             lens_radius,
             time_start,
             time_end,
         }
     }
 
-    pub fn ray<T: Rng>(&self, rng: &mut T, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * random_in_unit_disk(rng);
-        let offset = &self.u * rd.x() + &self.v * rd.y();
-        let direction = &self.lower_left_corner + s * &self.horizontal + t * &self.vertical
-            - &self.origin
-            - &offset;
+    pub fn builder() -> CameraBuilder {
+        CameraBuilder::default()
+    }
+
+    /// The ray through `(s, t)` on screen, from (0, 0) at the bottom left to (1, 1)
+    /// at the top right, or `None` if the projection doesn't cover that point.
+    pub fn ray<T: Rng>(&self, rng: &mut T, s: f64, t: f64) -> Option<Ray> {
+        let (origin, direction) = match self.projection {
+            Projection::Perspective => {
+                let rd = self.lens_radius * random_in_unit_disk(rng);
+                let offset = &self.u * rd.x() + &self.v * rd.y();
+                let direction = &self.lower_left_corner + s * &self.horizontal + t * &self.vertical
+                    - &self.origin
+                    - &offset;
+
+                (&self.origin + offset, direction)
+            }
+            Projection::Orthographic => {
+                let origin = &self.lower_left_corner + s * &self.horizontal + t * &self.vertical;
+                (origin, -self.w.clone())
+            }
+            Projection::Fisheye => {
+                // Scaled so the top and bottom edges are 1 away from the center
+                let x = (2.0 * s - 1.0) * self.aspect;
+                let y = 2.0 * t - 1.0;
+                let radius = (x * x + y * y).sqrt();
+
+                let theta = radius * self.vertical_fov / 2.0;
+                if theta > PI {
+                    return None;
+                }
 
-        let time =
-            self.time_start + (rng.random_double() as f64) * (self.time_end - self.time_start);
-        Ray::new(&self.origin + offset, direction, time)
+                let direction = if radius > 0.0 {
+                    let (sin_theta, cos_theta) = theta.sin_cos();
+                    (sin_theta * x / radius) * &self.u + (sin_theta * y / radius) * &self.v
+                        - cos_theta * &self.w
+                } else {
+                    -self.w.clone()
+                };
+
+                (self.origin.clone(), direction)
+            }
+            Projection::Equirectangular => {
+                let longitude = (s - 0.5) * 2.0 * PI;
+                let latitude = (t - 0.5) * PI;
+
+                let (sin_longitude, cos_longitude) = longitude.sin_cos();
+                let (sin_latitude, cos_latitude) = latitude.sin_cos();
+                let direction = (cos_latitude * sin_longitude) * &self.u + sin_latitude * &self.v
+                    - (cos_latitude * cos_longitude) * &self.w;
+
+                (self.origin.clone(), direction)
+            }
+        };
+
+        let time = self.time_start + rng.random_double() * (self.time_end - self.time_start);
+        Some(Ray::new(origin, direction, time))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CameraBuilder {
+    projection: Projection,
+    look_from: Vec3,
+    look_at: Vec3,
+    v_up: Vec3,
+    vertical_fov: f64,
+    aspect: f64,
+    aperture: f64,
+    focus_distance: f64,
+    time_start: f64,
+    time_end: f64,
+}
+
+impl CameraBuilder {
+    pub fn projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn look_from(mut self, look_from: Vec3) -> Self {
+        self.look_from = look_from;
+        self
+    }
+
+    pub fn look_at(mut self, look_at: Vec3) -> Self {
+        self.look_at = look_at;
+        self
+    }
+
+    pub fn v_up(mut self, v_up: Vec3) -> Self {
+        self.v_up = v_up;
+        self
+    }
+
+    /// In degrees.
+    pub fn vertical_fov(mut self, vertical_fov: f64) -> Self {
+        self.vertical_fov = vertical_fov;
+        self
+    }
+
+    /// Width over height, which should match the image being rendered.
+    pub fn aspect(mut self, aspect: f64) -> Self {
+        self.aspect = aspect;
+        self
+    }
+
+    pub fn aperture(mut self, aperture: f64) -> Self {
+        self.aperture = aperture;
+        self
+    }
+
+    pub fn focus_distance(mut self, focus_distance: f64) -> Self {
+        self.focus_distance = focus_distance;
+        self
+    }
+
+    /// Focuses on whatever in `scene` is seen at `(s, t)` on screen, from (0, 0) at
+    /// the bottom left to (1, 1) at the top right, looking through smoke and fog.
+    /// The focus distance is left alone if nothing is there.
+    pub fn autofocus(mut self, scene: &Scene, s: f64, t: f64) -> Self {
+        // With a pinhole focused at 1 unit, every ray reaches the focus plane at
+        // t = 1, so a hit's t is its distance along the view direction
+        let probe = self
+            .clone()
+            .projection(Projection::Perspective)
+            .aperture(0.0)
+            .focus_distance(1.0)
+            .build();
+        let mut rng = SmallRng::seed_from_u64(0);
+        let hit: Option<Hit<'_, SmallRng>> = probe.ray(&mut rng, s, t).and_then(|ray| {
+            scene
+                .world()
+                .surface_hit(&ray, 0.001, f64::INFINITY, &mut rng)
+        });
+        if let Some(hit) = hit {
+            self.focus_distance = hit.t();
+        }
+
+        self
+    }
+
+    pub fn time(mut self, time_start: f64, time_end: f64) -> Self {
+        self.time_start = time_start;
+        self.time_end = time_end;
+        self
+    }
+
+    pub fn build(self) -> Camera {
+        Camera::new(
+            self.projection,
+            self.look_from,
+            self.look_at,
+            self.v_up,
+            self.vertical_fov,
+            self.aspect,
+            self.aperture,
+            self.focus_distance,
+            self.time_start,
+            self.time_end,
+        )
+    }
+}
+
+impl Default for CameraBuilder {
+    fn default() -> Self {
+        Self {
+            projection: Projection::default(),
+            look_from: Vec3::default(),
+            look_at: Vec3::new(0.0, 0.0, -1.0),
+            v_up: Vec3::new(0.0, 1.0, 0.0),
+            vertical_fov: 90.0,
+            aspect: 1.0,
+            aperture: 0.0,
+            focus_distance: 1.0,
+            time_start: 0.0,
+            time_end: 1.0,
+        }
     }
 }
 
@@ -86,3 +291,31 @@ fn random_in_unit_disk<T: Rng>(rng: &mut T) -> Vec3 {
     }
     p
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable::Shape, material::Material};
+
+    #[test]
+    fn autofocus_looks_through_media() {
+        let material = || Material::lambertian(0.5, 0.5, 0.5);
+        let fog = Shape::constant_medium(Shape::sphere(0.0, 0.0, -5.0, 2.0, material()), 10.0);
+        let scene = Scene::builder()
+            .shape(fog)
+            .shape(Shape::sphere(0.0, 0.0, -10.0, 1.0, material()))
+            .build();
+
+        let builder = Camera::builder().autofocus(&scene, 0.5, 0.5);
+        assert!((builder.focus_distance - 9.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn autofocus_keeps_the_distance_when_nothing_is_there() {
+        let scene = Scene::builder().build();
+        let builder = Camera::builder()
+            .focus_distance(3.0)
+            .autofocus(&scene, 0.5, 0.5);
+        assert_eq!(builder.focus_distance, 3.0);
+    }
+}
@@ -0,0 +1,265 @@
+use std::{env, path::PathBuf};
+
+use ray_tracer::config::ConfigOverride;
+
+pub const USAGE: &str = "\
+Usage: ray_tracer [render|inspect] [CONFIG] [OPTIONS]
+
+Renders the scene described by CONFIG, or with `inspect` builds it and prints a
+summary instead. CONFIG defaults to $TRACER_CONFIG_PATH, then ./resources/tracer.toml.
+
+Options:
+      --width <PIXELS>     Sets renderer.screen_width
+      --height <PIXELS>    Sets renderer.screen_height
+      --spp <SAMPLES>      Sets renderer.antialias_iterations
+      --seed <SEED>        Sets renderer.seed
+      --threads <COUNT>    Sets renderer.threads
+      --output <PATH>      Sets output.output_path
+      --scene <NAME>       Replaces [world] with a preset, e.g. CornellBox
+      --set <KEY=VALUE>    Sets any field, e.g. camera.fov=30 or output.tone_map=\"aces\"
+      --dump-config        Prints the config with every override and default applied
+  -h, --help               Prints this message
+";
+
+const DEFAULT_CONFIG_PATH: &str = "./resources/tracer.toml";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Render,
+    Inspect,
+}
+
+#[derive(Debug)]
+pub struct Args {
+    command: Command,
+    config_path: PathBuf,
+    overrides: Vec<ConfigOverride>,
+    dump_config: bool,
+    help: bool,
+}
+
+impl Args {
+    /// Parses everything after the program name. Options are applied in order, so
+    /// later ones win.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut command = None;
+        let mut config_path = None;
+        let mut overrides = Vec::new();
+        let mut dump_config = false;
+        let mut help = false;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') {
+                match arg.as_str() {
+                    "render" if command.is_none() && config_path.is_none() => {
+                        command = Some(Command::Render)
+                    }
+                    "inspect" if command.is_none() && config_path.is_none() => {
+                        command = Some(Command::Inspect)
+                    }
+                    _ if config_path.is_none() => config_path = Some(PathBuf::from(arg)),
+                    _ => return Err(format!("Unexpected argument `{}`", arg)),
+                }
+                continue;
+            }
+
+            // Both `--width 100` and `--width=100` are accepted
+            let (flag, inline_value) = match arg.find('=') {
+                Some(index) => (arg[..index].to_string(), Some(arg[index + 1..].to_string())),
+                None => (arg, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("{} needs a value", flag))
+            };
+
+            let config_override = match flag.as_str() {
+                "--width" => number_override("renderer.screen_width", &flag, value()?)?,
+                "--height" => number_override("renderer.screen_height", &flag, value()?)?,
+                "--spp" => number_override("renderer.antialias_iterations", &flag, value()?)?,
+                "--seed" => number_override("renderer.seed", &flag, value()?)?,
+                "--threads" => number_override("renderer.threads", &flag, value()?)?,
+                "--output" => ConfigOverride::new("output.output_path", value()?),
+                "--scene" => {
+                    let mut world = toml::value::Table::new();
+                    world.insert("scene".to_string(), toml::Value::String(value()?));
+                    ConfigOverride::new("world", world)
+                }
+                "--set" => {
+                    let assignment = value()?;
+                    let (key, raw_value) = assignment
+                        .split_once('=')
+                        .ok_or_else(|| format!("--set expects KEY=VALUE, got `{}`", assignment))?;
+                    ConfigOverride::new(key.trim(), parse_value(raw_value.trim()))
+                }
+                "--dump-config" => {
+                    dump_config = true;
+                    continue;
+                }
+                "-h" | "--help" => {
+                    help = true;
+                    continue;
+                }
+                _ => return Err(format!("Unknown option `{}`", flag)),
+            };
+            overrides.push(config_override);
+        }
+
+        let config_path = config_path
+            .or_else(|| env::var_os("TRACER_CONFIG_PATH").map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+
+        Ok(Self {
+            command: command.unwrap_or(Command::Render),
+            config_path,
+            overrides,
+            dump_config,
+            help,
+        })
+    }
+
+    pub fn command(&self) -> Command {
+        self.command
+    }
+
+    pub fn config_path(&self) -> &PathBuf {
+        &self.config_path
+    }
+
+    pub fn overrides(&self) -> &[ConfigOverride] {
+        &self.overrides
+    }
+
+    pub fn dump_config(&self) -> bool {
+        self.dump_config
+    }
+
+    pub fn help(&self) -> bool {
+        self.help
+    }
+}
+
+fn number_override(key: &str, flag: &str, value: String) -> Result<ConfigOverride, String> {
+    // TOML integers are signed, so that's as large as the config can hold
+    let number: i64 = value
+        .parse()
+        .ok()
+        .filter(|&number| number >= 0)
+        .ok_or_else(|| format!("{} expects a whole number, got `{}`", flag, value))?;
+
+    Ok(ConfigOverride::new(key, number))
+}
+
+/// Reads `value` as it would be written in the config, so `30` is a number and
+/// `{ x = 1, y = 2, z = 3 }` a vector. Anything that isn't valid TOML is a string.
+fn parse_value(value: &str) -> toml::Value {
+    toml::from_str::<toml::value::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn overrides(args: &Args) -> Vec<(&str, &toml::Value)> {
+        args.overrides()
+            .iter()
+            .map(|config_override| (config_override.key(), config_override.value()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_the_command_and_config_path() {
+        let args = parse(&["inspect", "scene.toml", "--dump-config"]).unwrap();
+
+        assert_eq!(args.command(), Command::Inspect);
+        assert_eq!(args.config_path(), &PathBuf::from("scene.toml"));
+        assert!(args.dump_config());
+        assert!(!args.help());
+    }
+
+    #[test]
+    fn turns_flags_into_overrides_in_order() {
+        let args = parse(&[
+            "scene.toml",
+            "--width",
+            "320",
+            "--height=240",
+            "--output",
+            "out.png",
+            "--seed=0",
+        ])
+        .unwrap();
+
+        assert_eq!(args.command(), Command::Render);
+        assert_eq!(
+            overrides(&args),
+            vec![
+                ("renderer.screen_width", &toml::Value::Integer(320)),
+                ("renderer.screen_height", &toml::Value::Integer(240)),
+                ("output.output_path", &toml::Value::from("out.png")),
+                ("renderer.seed", &toml::Value::Integer(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn set_reads_values_as_toml() {
+        let args = parse(&[
+            "scene.toml",
+            "--set",
+            "camera.fov=30",
+            "--set",
+            "camera.look_at = { x = 1, y = 2, z = 3 }",
+            "--set=output.tone_map=aces",
+        ])
+        .unwrap();
+
+        let look_at: toml::Value = toml::from_str("x = 1\ny = 2\nz = 3").unwrap();
+        assert_eq!(
+            overrides(&args),
+            vec![
+                ("camera.fov", &toml::Value::Integer(30)),
+                ("camera.look_at", &look_at),
+                ("output.tone_map", &toml::Value::from("aces")),
+            ]
+        );
+    }
+
+    #[test]
+    fn scene_replaces_the_world_table() {
+        let args = parse(&["scene.toml", "--scene", "CornellSmoke"]).unwrap();
+
+        let world: toml::Value = toml::from_str("scene = \"CornellSmoke\"").unwrap();
+        assert_eq!(overrides(&args), vec![("world", &world)]);
+    }
+
+    #[test]
+    fn rejects_negative_numbers() {
+        let error = parse(&["scene.toml", "--width", "-5"]).unwrap_err();
+        assert!(error.contains("--width"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_numbers_too_large_for_toml() {
+        let error = parse(&["scene.toml", "--seed", "9223372036854775808"]).unwrap_err();
+        assert!(error.contains("--seed"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse(&["scene.toml", "--spp"]).is_err());
+        assert!(parse(&["scene.toml", "--set", "camera.fov"]).is_err());
+        assert!(parse(&["scene.toml", "--frobnicate"]).is_err());
+        assert!(parse(&["scene.toml", "other.toml"]).is_err());
+    }
+}
@@ -1,15 +1,33 @@
-use std::{fs::File, io::Read, path::Path};
-
-use serde_derive::Deserialize;
-
-use crate::vec3::Vec3;
-
-#[derive(Debug, Default, Deserialize)]
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    animation::Curve,
+    bvh::SplitMethod,
+    camera::Projection,
+    error::{Error, Result},
+    output::FileType,
+    tonemap::ToneMap,
+    vec3::Vec3,
+};
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct TracerConfig {
     renderer: RendererConfig,
     camera: CameraConfig,
     output: OutputConfig,
     world: WorldConfig,
+    #[serde(default)]
+    background: BackgroundConfig,
+    #[serde(default)]
+    animation: Option<AnimationConfig>,
+    #[serde(flatten)]
+    scene: SceneConfig,
 }
 
 impl TracerConfig {
@@ -28,10 +46,76 @@ impl TracerConfig {
     pub fn world_config(&self) -> &WorldConfig {
         &self.world
     }
+
+    pub fn background_config(&self) -> &BackgroundConfig {
+        &self.background
+    }
+
+    /// Set when the config describes a sequence of frames rather than one image.
+    pub fn animation_config(&self) -> Option<&AnimationConfig> {
+        self.animation.as_ref()
+    }
+
+    pub fn scene_config(&self) -> &SceneConfig {
+        &self.scene
+    }
+
+    /// The config as TOML, with every default filled in.
+    pub fn to_toml_string(&self) -> String {
+        // Going through a `Value` puts plain values ahead of tables, which TOML requires
+        let value = toml::Value::try_from(self).expect("configs only hold TOML types");
+        toml::to_string_pretty(&value).expect("a TOML value can always be written")
+    }
+}
+
+/// Sets the field at `key`, a dotted path like `renderer.screen_width`, replacing
+/// whatever the config file had there.
+#[derive(Clone, Debug)]
+pub struct ConfigOverride {
+    key: String,
+    value: toml::Value,
+}
+
+impl ConfigOverride {
+    pub fn new<K: Into<String>, V: Into<toml::Value>>(key: K, value: V) -> Self {
+        Self {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn value(&self) -> &toml::Value {
+        &self.value
+    }
+
+    fn apply(&self, config: &mut toml::value::Table) {
+        let mut keys: Vec<&str> = self.key.split('.').collect();
+        let last = keys.pop().unwrap_or_default();
+
+        let mut table = config;
+        for key in keys {
+            let entry = table
+                .entry(key)
+                .or_insert_with(|| toml::Value::Table(toml::value::Table::new()));
+            if !entry.is_table() {
+                *entry = toml::Value::Table(toml::value::Table::new());
+            }
+
+            table = entry.as_table_mut().expect("just replaced with a table");
+        }
+
+        table.insert(last.to_string(), self.value.clone());
+    }
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct CameraConfig {
+    #[serde(default)]
+    projection: Projection,
     #[serde(default = "CameraConfig::default_fov")]
     fov: f64,
     #[serde(default = "CameraConfig::default_aperture")]
@@ -40,6 +124,19 @@ pub struct CameraConfig {
     look_from: Vec3,
     #[serde(default = "CameraConfig::default_look_at")]
     look_at: Vec3,
+    #[serde(default = "CameraConfig::default_v_up")]
+    v_up: Vec3,
+    /// Defaults to the distance between `look_from` and `look_at`.
+    #[serde(default)]
+    focus_distance: Option<f64>,
+    /// Focuses on whatever is seen through `focus_point` instead, keeping
+    /// `focus_distance` if nothing is there.
+    #[serde(default)]
+    autofocus: bool,
+    /// Where on screen to focus, from (0, 0) at the bottom left to (1, 1) at the
+    /// top right.
+    #[serde(default = "CameraConfig::default_focus_point")]
+    focus_point: [f64; 2],
     #[serde(default = "CameraConfig::default_time_start")]
     time_start: f64,
     #[serde(default = "CameraConfig::default_time_end")]
@@ -67,6 +164,16 @@ impl CameraConfig {
         Vec3::default()
     }
 
+    #[allow(dead_code)]
+    fn default_v_up() -> Vec3 {
+        Vec3::new(0.0, 1.0, 0.0)
+    }
+
+    #[allow(dead_code)]
+    fn default_focus_point() -> [f64; 2] {
+        [0.5, 0.5]
+    }
+
     #[allow(dead_code)]
     fn default_time_start() -> f64 {
         0.0
@@ -77,6 +184,10 @@ impl CameraConfig {
         1.0
     }
 
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
     pub fn fov(&self) -> f64 {
         self.fov
     }
@@ -93,6 +204,23 @@ impl CameraConfig {
         &self.look_at
     }
 
+    pub fn v_up(&self) -> &Vec3 {
+        &self.v_up
+    }
+
+    pub fn focus_distance(&self) -> f64 {
+        self.focus_distance
+            .unwrap_or_else(|| (&self.look_from - &self.look_at).length())
+    }
+
+    pub fn autofocus(&self) -> bool {
+        self.autofocus
+    }
+
+    pub fn focus_point(&self) -> (f64, f64) {
+        (self.focus_point[0], self.focus_point[1])
+    }
+
     pub fn time_start(&self) -> f64 {
         self.time_start
     }
@@ -102,7 +230,88 @@ impl CameraConfig {
     }
 }
 
-#[derive(Debug, Default, Deserialize)]
+/// Renders `frames` images, one every `1 / fps` seconds, each to its own numbered
+/// file. Objects animate through their `motion` keyframes, whose times are in
+/// seconds, and the camera through the keyframes here.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AnimationConfig {
+    frames: usize,
+    #[serde(default = "AnimationConfig::default_fps")]
+    fps: f64,
+    /// How much of each frame's duration the shutter is open for, blurring
+    /// whatever moves in that time.
+    #[serde(default = "AnimationConfig::default_shutter")]
+    shutter: f64,
+    #[serde(default)]
+    camera: Vec<CameraKeyframeConfig>,
+}
+
+impl AnimationConfig {
+    #[allow(dead_code)]
+    fn default_fps() -> f64 {
+        24.0
+    }
+
+    #[allow(dead_code)]
+    fn default_shutter() -> f64 {
+        0.5
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    pub fn shutter(&self) -> f64 {
+        self.shutter
+    }
+
+    pub fn camera(&self) -> &[CameraKeyframeConfig] {
+        &self.camera
+    }
+}
+
+/// Anything left out stays as it was at the keyframe before.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CameraKeyframeConfig {
+    time: f64,
+    #[serde(default)]
+    look_from: Option<Vec3>,
+    #[serde(default)]
+    look_at: Option<Vec3>,
+    #[serde(default)]
+    fov: Option<f64>,
+    /// Eases the way to the next keyframe.
+    #[serde(default)]
+    curve: Curve,
+}
+
+impl CameraKeyframeConfig {
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn look_from(&self) -> Option<&Vec3> {
+        self.look_from.as_ref()
+    }
+
+    pub fn look_at(&self) -> Option<&Vec3> {
+        self.look_at.as_ref()
+    }
+
+    pub fn fov(&self) -> Option<f64> {
+        self.fov
+    }
+
+    pub fn curve(&self) -> Curve {
+        self.curve
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct RendererConfig {
     #[serde(default = "RendererConfig::default_screen_width")]
     screen_width: usize,
@@ -114,6 +323,14 @@ pub struct RendererConfig {
     render_parallel: bool,
     #[serde(default)]
     use_bounding_volume: bool,
+    #[serde(default)]
+    bvh_split: SplitMethod,
+    /// Renders with the same seed are identical, otherwise one is picked at random.
+    #[serde(default)]
+    seed: Option<u64>,
+    /// Defaults to one per core.
+    #[serde(default)]
+    threads: Option<usize>,
 }
 
 impl RendererConfig {
@@ -151,19 +368,87 @@ impl RendererConfig {
     pub fn use_bounding_volume(&self) -> bool {
         self.use_bounding_volume
     }
+
+    pub fn bvh_split(&self) -> SplitMethod {
+        self.bvh_split
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn threads(&self) -> Option<usize> {
+        self.threads
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackgroundConfig {
+    Constant {
+        color: Vec3,
+    },
+    Gradient {
+        #[serde(default = "BackgroundConfig::default_bottom")]
+        bottom: Vec3,
+        #[serde(default = "BackgroundConfig::default_top")]
+        top: Vec3,
+    },
+    /// An equirectangular image, ideally a Radiance `.hdr` file.
+    Environment {
+        path: String,
+        #[serde(default)]
+        rotation: f64,
+        #[serde(default = "BackgroundConfig::default_intensity")]
+        intensity: f64,
+    },
+}
+
+impl BackgroundConfig {
+    #[allow(dead_code)]
+    fn default_bottom() -> Vec3 {
+        Vec3::new(1.0, 1.0, 1.0)
+    }
+
+    #[allow(dead_code)]
+    fn default_top() -> Vec3 {
+        Vec3::new(0.5, 0.7, 1.0)
+    }
+
+    #[allow(dead_code)]
+    fn default_intensity() -> f64 {
+        1.0
+    }
 }
 
-#[derive(Debug, Default, Deserialize)]
+impl Default for BackgroundConfig {
+    fn default() -> Self {
+        BackgroundConfig::Constant {
+            color: Vec3::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct OutputConfig {
     #[serde(default = "OutputConfig::default_output_path")]
     output_path: String,
 
+    #[allow(dead_code)]
     #[serde(default)]
     file_name: String,
 
+    #[serde(default, alias = "type")]
+    file_type: Option<FileType>,
+
+    #[serde(default)]
+    tone_map: ToneMap,
+
+    /// In stops, so each step doubles or halves the brightness.
     #[serde(default)]
-    file_type: String,
+    exposure: f64,
 
+    #[allow(dead_code)]
     #[serde(default)]
     render_window: bool,
 }
@@ -177,11 +462,24 @@ impl OutputConfig {
     pub fn output_path(&self) -> &str {
         &self.output_path
     }
+
+    pub fn file_type(&self) -> Option<FileType> {
+        self.file_type
+    }
+
+    pub fn tone_map(&self) -> ToneMap {
+        self.tone_map
+    }
+
+    pub fn exposure(&self) -> f64 {
+        self.exposure
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 #[serde(tag = "scene")]
 pub enum WorldConfig {
+    #[default]
     Basic,
     Dynamic {
         #[serde(default = "WorldConfig::default_max_objects")]
@@ -192,6 +490,8 @@ pub enum WorldConfig {
     Earth,
     SimpleLight,
     CornellBox,
+    CornellSmoke,
+    Custom,
 }
 
 impl WorldConfig {
@@ -201,17 +501,436 @@ impl WorldConfig {
     }
 }
 
-impl Default for WorldConfig {
-    fn default() -> Self {
-        Self::Basic
+/// Scene description used by `WorldConfig::Custom`.
+///
+/// Textures and materials are declared in named tables and referenced by id, so a
+/// single material can be shared between any number of objects.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SceneConfig {
+    #[serde(default)]
+    textures: BTreeMap<String, TextureConfig>,
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialConfig>,
+    #[serde(default)]
+    prototypes: BTreeMap<String, PrototypeConfig>,
+    #[serde(default)]
+    objects: Vec<ObjectConfig>,
+}
+
+impl SceneConfig {
+    pub fn textures(&self) -> &BTreeMap<String, TextureConfig> {
+        &self.textures
+    }
+
+    pub fn materials(&self) -> &BTreeMap<String, MaterialConfig> {
+        &self.materials
     }
+
+    pub fn prototypes(&self) -> &BTreeMap<String, PrototypeConfig> {
+        &self.prototypes
+    }
+
+    pub fn objects(&self) -> &[ObjectConfig] {
+        &self.objects
+    }
+}
+
+/// Geometry shared by every `instance` object that references it by id.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PrototypeConfig {
+    objects: Vec<ObjectConfig>,
+}
+
+impl PrototypeConfig {
+    pub fn objects(&self) -> &[ObjectConfig] {
+        &self.objects
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextureConfig {
+    Constant {
+        color: Vec3,
+    },
+    Checker {
+        even: String,
+        odd: String,
+    },
+    Noise {
+        #[serde(default = "TextureConfig::default_scale")]
+        scale: f64,
+    },
+    Image {
+        path: String,
+    },
+}
+
+impl TextureConfig {
+    #[allow(dead_code)]
+    fn default_scale() -> f64 {
+        1.0
+    }
+}
+
+/// Colored materials take either a constant `albedo`/`emit` color or the id of a
+/// texture declared in `[textures]`, but not both.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MaterialConfig {
+    Lambertian {
+        albedo: Option<Vec3>,
+        texture: Option<String>,
+    },
+    Metal {
+        albedo: Vec3,
+        #[serde(default)]
+        fuzziness: f64,
+    },
+    Dielectric {
+        ref_idx: f64,
+    },
+    DiffuseLight {
+        emit: Option<Vec3>,
+        texture: Option<String>,
+    },
+    /// Scatters light evenly in every direction, for filling objects with a `density`.
+    Isotropic {
+        albedo: Option<Vec3>,
+        texture: Option<String>,
+    },
+    /// Like `isotropic`, but leaning towards scattering light onwards with a
+    /// positive `anisotropy` or back with a negative one, between -1 and 1.
+    HenyeyGreenstein {
+        albedo: Option<Vec3>,
+        texture: Option<String>,
+        #[serde(default)]
+        anisotropy: f64,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ObjectConfig {
+    #[serde(flatten)]
+    shape: ShapeConfig,
+    transform: Option<TransformConfig>,
+    motion: Option<MotionConfig>,
+    /// Fills the object, which has to be closed, with smoke or fog this thick that
+    /// scatters with its material instead of giving it a surface. Thickness is
+    /// measured in the object's own units, so scaling it keeps how it looks.
+    density: Option<f64>,
+    /// Varies the `density` through the object, which is then the thickest it gets.
+    density_field: Option<DensityFieldConfig>,
+}
+
+impl ObjectConfig {
+    pub fn shape(&self) -> &ShapeConfig {
+        &self.shape
+    }
+
+    pub fn transform(&self) -> Option<&TransformConfig> {
+        self.transform.as_ref()
+    }
+
+    pub fn motion(&self) -> Option<&MotionConfig> {
+        self.motion.as_ref()
+    }
+
+    pub fn density(&self) -> Option<f64> {
+        self.density
+    }
+
+    pub fn density_field(&self) -> Option<&DensityFieldConfig> {
+        self.density_field.as_ref()
+    }
+}
+
+/// How thick an object's medium is from place to place, from 0 for empty to 1 for
+/// its full `density`. The field is laid out in the object's own space, so it
+/// turns and moves along with the object.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DensityFieldConfig {
+    /// Perlin turbulence with about `scale` features per unit, adding up `depth`
+    /// octaves of detail.
+    Turbulence {
+        #[serde(default = "DensityFieldConfig::default_scale")]
+        scale: f64,
+        #[serde(default = "DensityFieldConfig::default_depth")]
+        depth: usize,
+    },
+    /// A Mitsuba `.vol` voxel grid stretched over the object's bounds before its
+    /// `transform`, whose values multiply the density.
+    Grid { path: String },
+}
+
+impl DensityFieldConfig {
+    #[allow(dead_code)]
+    fn default_scale() -> f64 {
+        1.0
+    }
+
+    #[allow(dead_code)]
+    fn default_depth() -> usize {
+        7
+    }
+}
+
+/// Moves an object, after its `transform`, for motion blur or animation. Between
+/// keyframes the translation and rotation follow each keyframe's `curve`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MotionConfig {
+    /// What the object rotates about, by default the center of its bounds.
+    #[serde(default)]
+    pivot: Option<Vec3>,
+    keyframes: Vec<KeyframeConfig>,
+}
+
+impl MotionConfig {
+    pub fn pivot(&self) -> Option<&Vec3> {
+        self.pivot.as_ref()
+    }
+
+    pub fn keyframes(&self) -> &[KeyframeConfig] {
+        &self.keyframes
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KeyframeConfig {
+    time: f64,
+    #[serde(default)]
+    translate: Vec3,
+    /// About X, Y and Z in degrees.
+    #[serde(default)]
+    rotate: Vec3,
+    /// Eases the way to the next keyframe.
+    #[serde(default)]
+    curve: Curve,
+}
+
+impl KeyframeConfig {
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn translate(&self) -> &Vec3 {
+        &self.translate
+    }
+
+    pub fn rotate(&self) -> &Vec3 {
+        &self.rotate
+    }
+
+    pub fn curve(&self) -> Curve {
+        self.curve
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShapeConfig {
+    Sphere {
+        center: Vec3,
+        radius: f64,
+        material: String,
+    },
+    MovingSphere {
+        center_start: Vec3,
+        center_end: Vec3,
+        radius: f64,
+        material: String,
+        #[serde(default = "ShapeConfig::default_time_start")]
+        time_start: f64,
+        #[serde(default = "ShapeConfig::default_time_end")]
+        time_end: f64,
+    },
+    XyRect {
+        x0: f64,
+        x1: f64,
+        y0: f64,
+        y1: f64,
+        k: f64,
+        material: String,
+    },
+    XzRect {
+        x0: f64,
+        x1: f64,
+        z0: f64,
+        z1: f64,
+        k: f64,
+        material: String,
+    },
+    YzRect {
+        y0: f64,
+        y1: f64,
+        z0: f64,
+        z1: f64,
+        k: f64,
+        material: String,
+    },
+    Cube {
+        min: Vec3,
+        max: Vec3,
+        material: String,
+    },
+    Triangle {
+        v0: Vec3,
+        v1: Vec3,
+        v2: Vec3,
+        material: String,
+    },
+    Mesh {
+        positions: Vec<Vec3>,
+        indices: Vec<[usize; 3]>,
+        material: String,
+    },
+    /// Wavefront OBJ file. `material` is used for faces without an MTL material.
+    Obj {
+        path: String,
+        material: Option<String>,
+    },
+    /// Places a prototype declared in `[prototypes]`, optionally replacing all of its
+    /// materials with `material`.
+    Instance {
+        prototype: String,
+        material: Option<String>,
+    },
+}
+
+impl ShapeConfig {
+    #[allow(dead_code)]
+    fn default_time_start() -> f64 {
+        0.0
+    }
+
+    #[allow(dead_code)]
+    fn default_time_end() -> f64 {
+        1.0
+    }
+}
+
+/// Applied as scale, then rotation about X, Y and Z in degrees, then translation.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TransformConfig {
+    #[serde(default)]
+    translate: Vec3,
+    #[serde(default)]
+    rotate: Vec3,
+    #[serde(default = "TransformConfig::default_scale")]
+    scale: Vec3,
 }
 
-pub fn read_tracer_config<P: AsRef<Path>>(input_path: P) -> TracerConfig {
-    let mut config_file = File::open(input_path).unwrap();
+impl TransformConfig {
+    #[allow(dead_code)]
+    fn default_scale() -> Vec3 {
+        Vec3::new(1.0, 1.0, 1.0)
+    }
+
+    pub fn translate(&self) -> &Vec3 {
+        &self.translate
+    }
 
-    let mut buffer = String::new();
-    config_file.read_to_string(&mut buffer);
+    pub fn rotate(&self) -> &Vec3 {
+        &self.rotate
+    }
+
+    pub fn scale(&self) -> &Vec3 {
+        &self.scale
+    }
+}
+
+pub fn read_tracer_config<P: AsRef<Path>>(input_path: P) -> Result<TracerConfig> {
+    read_tracer_config_with_overrides(input_path, &[])
+}
+
+pub fn read_tracer_config_with_overrides<P: AsRef<Path>>(
+    input_path: P,
+    overrides: &[ConfigOverride],
+) -> Result<TracerConfig> {
+    let input_path = input_path.as_ref();
+    let buffer = fs::read_to_string(input_path).map_err(|e| Error::io(input_path, e))?;
+    let config_error = |source| Error::Config {
+        path: input_path.to_path_buf(),
+        source,
+    };
+
+    // Errors only point at the right line when parsed straight from the file
+    if overrides.is_empty() {
+        return toml::from_str(&buffer).map_err(config_error);
+    }
+
+    let mut config: toml::value::Table = toml::from_str(&buffer).map_err(config_error)?;
+    for config_override in overrides {
+        config_override.apply(&mut config);
+    }
+
+    // Deserializing a `Value` directly can't handle internally tagged enums, so the
+    // merged config goes back through text
+    let merged =
+        toml::to_string(&toml::Value::Table(config)).expect("a TOML value can always be written");
+    toml::from_str(&merged).map_err(|source| Error::Config {
+        path: PathBuf::from(format!("{} with overrides", input_path.display())),
+        source,
+    })
+}
 
-    toml::from_str(&buffer).unwrap()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(source: &str) -> toml::value::Table {
+        toml::from_str(source).unwrap()
+    }
+
+    #[test]
+    fn override_replaces_a_nested_value() {
+        let mut config = table("[renderer]\nscreen_width = 100\nscreen_height = 50\n");
+        ConfigOverride::new("renderer.screen_width", 300).apply(&mut config);
+
+        assert_eq!(
+            config,
+            table("[renderer]\nscreen_width = 300\nscreen_height = 50\n")
+        );
+    }
+
+    #[test]
+    fn override_creates_missing_tables() {
+        let mut config = table("");
+        ConfigOverride::new("camera.look_at.y", 2.5).apply(&mut config);
+
+        assert_eq!(config, table("[camera.look_at]\ny = 2.5\n"));
+    }
+
+    #[test]
+    fn override_replaces_values_in_the_way_of_a_table() {
+        let mut config = table("camera = 1\n");
+        ConfigOverride::new("camera.fov", 30).apply(&mut config);
+
+        assert_eq!(config, table("[camera]\nfov = 30\n"));
+    }
+
+    #[test]
+    fn overrides_apply_in_order_over_the_file() {
+        let overrides = [
+            ConfigOverride::new("renderer.screen_width", 64),
+            ConfigOverride::new("renderer.seed", 7),
+            ConfigOverride::new("renderer.screen_width", 32),
+            ConfigOverride::new("output.output_path", "out.png"),
+        ];
+        let config =
+            read_tracer_config_with_overrides("resources/tracer.toml", &overrides).unwrap();
+
+        assert_eq!(config.renderer_config().screen_width(), 32);
+        assert_eq!(config.renderer_config().seed(), Some(7));
+        assert_eq!(config.output_config().output_path(), "out.png");
+    }
+
+    #[test]
+    fn overrides_of_the_wrong_type_are_config_errors() {
+        let overrides = [ConfigOverride::new("renderer.screen_width", "wide")];
+        let result = read_tracer_config_with_overrides("resources/tracer.toml", &overrides);
+
+        assert!(matches!(result, Err(Error::Config { .. })));
+    }
 }
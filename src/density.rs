@@ -0,0 +1,296 @@
+use std::{fs, io, path::Path};
+
+use rand::Rng;
+
+use crate::{
+    aabb::AABB,
+    error::{Error, Result},
+    perlin::Perlin,
+    vec3::Vec3,
+};
+
+/// How thick a heterogeneous medium is at each point, relative to its density.
+#[derive(Clone, Debug)]
+pub enum DensityField {
+    /// Perlin turbulence, which gives billowing smoke and clouds.
+    Turbulence {
+        noise: Box<Perlin>,
+        scale: f64,
+        depth: usize,
+    },
+    Grid(VoxelGrid),
+}
+
+impl DensityField {
+    /// `scale` is how many noise features there are per unit, and `depth` how many
+    /// octaves are added together.
+    pub fn turbulence<T: Rng>(rng: &mut T, scale: f64, depth: usize) -> Self {
+        Self::Turbulence {
+            noise: Box::new(Perlin::new(rng)),
+            scale,
+            depth,
+        }
+    }
+
+    pub fn grid(grid: VoxelGrid) -> Self {
+        Self::Grid(grid)
+    }
+
+    pub fn value(&self, p: &Vec3) -> f64 {
+        match self {
+            DensityField::Turbulence {
+                noise,
+                scale,
+                depth,
+            } => noise.turbulence(&(*scale * p), *depth).min(1.0),
+            DensityField::Grid(grid) => grid.value(p),
+        }
+    }
+
+    /// The most `value` can be anywhere.
+    pub fn max(&self) -> f64 {
+        match self {
+            DensityField::Turbulence { .. } => 1.0,
+            DensityField::Grid(grid) => grid.max,
+        }
+    }
+}
+
+/// Densities sampled on a regular grid, stretched over `bounds` and blended
+/// trilinearly between voxel centers. Outside the bounds it's empty.
+#[derive(Clone, Debug)]
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    /// X varies fastest, then Y, then Z.
+    values: Vec<f64>,
+    max: f64,
+    bounds: AABB,
+}
+
+impl VoxelGrid {
+    /// Reads a Mitsuba `.vol` grid of 32-bit floats, keeping only its first
+    /// channel. The bounds stored in the file are ignored in favour of `bounds`.
+    pub fn open<P: AsRef<Path>>(path: P, bounds: AABB) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|e| Error::io(path, e))?;
+        let invalid = |message: &str| {
+            let source = io::Error::new(io::ErrorKind::InvalidData, message);
+            Error::io(path, source)
+        };
+
+        let header = |index: usize| -> Option<u32> {
+            let start = 4 + 4 * index;
+            let word = bytes.get(start..start + 4)?;
+            Some(u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        };
+
+        if !bytes.starts_with(b"VOL\x03") {
+            return Err(invalid("not a version 3 .vol grid"));
+        }
+        if header(0) != Some(1) {
+            return Err(invalid("only grids of 32-bit floats are supported"));
+        }
+
+        let dimension = |index| header(index).ok_or_else(|| invalid("header is cut short"));
+        let resolution = [
+            dimension(1)? as usize,
+            dimension(2)? as usize,
+            dimension(3)? as usize,
+        ];
+        let channels = dimension(4)? as usize;
+        if resolution.contains(&0) || channels == 0 {
+            return Err(invalid("grid is empty"));
+        }
+
+        // The header is 48 bytes, ending with the bounds. A grid too big to count
+        // can't be in the file either.
+        let count = resolution[0]
+            .checked_mul(resolution[1])
+            .and_then(|count| count.checked_mul(resolution[2]));
+        let size = count
+            .and_then(|count| count.checked_mul(channels))
+            .and_then(|size| size.checked_mul(4));
+        let (count, size) = match (count, size) {
+            (Some(count), Some(size)) => (count, size),
+            _ => return Err(invalid("grid data is cut short")),
+        };
+        let data = &bytes[48.min(bytes.len())..];
+        if data.len() < size {
+            return Err(invalid("grid data is cut short"));
+        }
+
+        let values: Vec<f64> = data
+            .chunks_exact(4 * channels)
+            .take(count)
+            .map(|voxel| f32::from_le_bytes([voxel[0], voxel[1], voxel[2], voxel[3]]) as f64)
+            .map(|value| {
+                if value.is_finite() {
+                    value.max(0.0)
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        if values.len() != count {
+            return Err(invalid("grid data is cut short"));
+        }
+        let max = values.iter().cloned().fold(0.0, f64::max);
+
+        Ok(Self {
+            resolution,
+            values,
+            max,
+            bounds,
+        })
+    }
+
+    pub fn value(&self, p: &Vec3) -> f64 {
+        let mut cell = [0; 3];
+        let mut fraction = [0.0; 3];
+        for axis in 0..3 {
+            let (min, max) = (self.bounds.min()[axis], self.bounds.max()[axis]);
+            let f = (p[axis] - min) / (max - min);
+            if !(0.0..=1.0).contains(&f) {
+                return 0.0;
+            }
+
+            // Voxel centers sit half a voxel in from the bounds
+            let x = (f * self.resolution[axis] as f64 - 0.5)
+                .clamp(0.0, (self.resolution[axis] - 1) as f64);
+            cell[axis] = (x as usize).min(self.resolution[axis].saturating_sub(2));
+            fraction[axis] = x - cell[axis] as f64;
+        }
+
+        let mut value = 0.0;
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut index = [0; 3];
+            for axis in 0..3 {
+                let step = (corner >> axis) & 1;
+                index[axis] = (cell[axis] + step).min(self.resolution[axis] - 1);
+                weight *= if step == 1 {
+                    fraction[axis]
+                } else {
+                    1.0 - fraction[axis]
+                };
+            }
+
+            let [x, y, z] = index;
+            value += weight * self.values[(z * self.resolution[1] + y) * self.resolution[0] + x];
+        }
+
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::PathBuf};
+
+    use super::*;
+
+    /// Writes a `.vol` grid with the given header and data to a temporary file.
+    fn write_grid(name: &str, resolution: [u32; 3], channels: u32, data: &[f32]) -> PathBuf {
+        let mut bytes = b"VOL\x03".to_vec();
+        for word in &[1, resolution[0], resolution[1], resolution[2], channels] {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        for bound in &[0.0_f32, 0.0, 0.0, 1.0, 1.0, 1.0] {
+            bytes.extend_from_slice(&bound.to_le_bytes());
+        }
+        for value in data {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let path = env::temp_dir().join(format!("ray_tracer_density_{}.vol", name));
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn unit_bounds() -> AABB {
+        AABB::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    fn assert_invalid(result: Result<VoxelGrid>, expected: &str) {
+        match result {
+            Err(Error::Io { source, .. }) => {
+                assert_eq!(source.kind(), io::ErrorKind::InvalidData);
+                assert_eq!(source.to_string(), expected);
+            }
+            other => panic!("expected invalid data, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reads_the_first_channel() {
+        let data = [1.0, 10.0, -2.0, 20.0, f32::NAN, 30.0, 4.0, 40.0];
+        let path = write_grid("channels", [2, 2, 1], 2, &data);
+        let grid = VoxelGrid::open(&path, unit_bounds()).unwrap();
+
+        assert_eq!(grid.resolution, [2, 2, 1]);
+        assert_eq!(grid.values, vec![1.0, 0.0, 0.0, 4.0]);
+        assert_eq!(grid.max, 4.0);
+    }
+
+    #[test]
+    fn values_at_voxel_centers_are_exact() {
+        let data: Vec<f32> = (0..8).map(|i| i as f32).collect();
+        let path = write_grid("centers", [2, 2, 2], 1, &data);
+        let grid = VoxelGrid::open(&path, unit_bounds()).unwrap();
+
+        for z in 0..2 {
+            for y in 0..2 {
+                for x in 0..2 {
+                    let center = Vec3::new(
+                        0.25 + 0.5 * x as f64,
+                        0.25 + 0.5 * y as f64,
+                        0.25 + 0.5 * z as f64,
+                    );
+                    let expected = (x + 2 * y + 4 * z) as f64;
+                    assert!((grid.value(&center) - expected).abs() < 1e-9);
+                }
+            }
+        }
+
+        // Halfway between centers blends them, and outside the bounds is empty
+        assert!((grid.value(&Vec3::new(0.5, 0.25, 0.25)) - 0.5).abs() < 1e-9);
+        assert!((grid.value(&Vec3::new(0.5, 0.5, 0.5)) - 3.5).abs() < 1e-9);
+        assert_eq!(grid.value(&Vec3::new(1.5, 0.5, 0.5)), 0.0);
+    }
+
+    #[test]
+    fn rejects_truncated_grids() {
+        let path = write_grid("truncated", [2, 2, 2], 1, &[1.0; 7]);
+        assert_invalid(
+            VoxelGrid::open(&path, unit_bounds()),
+            "grid data is cut short",
+        );
+    }
+
+    #[test]
+    fn rejects_grids_too_big_to_count() {
+        let path = write_grid(
+            "overflow",
+            [u32::MAX, u32::MAX, u32::MAX],
+            u32::MAX,
+            &[1.0; 8],
+        );
+        assert_invalid(
+            VoxelGrid::open(&path, unit_bounds()),
+            "grid data is cut short",
+        );
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        let path = env::temp_dir().join("ray_tracer_density_format.vol");
+        fs::write(&path, b"VOL\x02").unwrap();
+        assert_invalid(
+            VoxelGrid::open(&path, unit_bounds()),
+            "not a version 3 .vol grid",
+        );
+
+        let path = write_grid("empty", [2, 0, 2], 1, &[]);
+        assert_invalid(VoxelGrid::open(&path, unit_bounds()), "grid is empty");
+    }
+}
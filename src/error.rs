@@ -0,0 +1,83 @@
+use std::{
+    error, fmt, io,
+    path::{Path, PathBuf},
+    result,
+};
+
+pub type Result<T> = result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// A file couldn't be opened, read or written.
+    Io { path: PathBuf, source: io::Error },
+    /// The config isn't valid TOML or doesn't match what the tracer expects.
+    Config {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// An image couldn't be decoded or encoded.
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    /// A line of an OBJ or MTL file couldn't be understood.
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// The scene refers to something that doesn't exist or can't be built.
+    Scene(String),
+}
+
+impl Error {
+    pub fn io<P: AsRef<Path>>(path: P, source: io::Error) -> Self {
+        Error::Io {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn image<P: AsRef<Path>>(path: P, source: image::ImageError) -> Self {
+        // Failing to open the file isn't a decoding problem
+        match source {
+            image::ImageError::IoError(source) => Error::io(path, source),
+            source => Error::Image {
+                path: path.as_ref().to_path_buf(),
+                source,
+            },
+        }
+    }
+
+    pub fn scene<S: Into<String>>(message: S) -> Self {
+        Error::Scene(message.into())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            // TOML errors already say which line and column they're on
+            Error::Config { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Image { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            Error::Scene(message) => f.write_str(message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Config { source, .. } => Some(source),
+            Error::Image { source, .. } => Some(source),
+            Error::Parse { .. } | Error::Scene(_) => None,
+        }
+    }
+}
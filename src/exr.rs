@@ -0,0 +1,221 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::vec3::Vec3;
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+
+const PIXEL_TYPE_FLOAT: i32 = 2;
+const NO_COMPRESSION: u8 = 0;
+const INCREASING_Y: u8 = 0;
+
+/// Writes a single part, uncompressed scanline OpenEXR file with 32-bit float
+/// RGB channels. `pixels` is in row-major order starting at the top row.
+pub fn create<P: AsRef<Path>>(
+    output_path: P,
+    image_x: usize,
+    image_y: usize,
+    pixels: &[Vec3],
+) -> io::Result<()> {
+    let mut output = BufWriter::new(File::create(output_path)?);
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION);
+
+    // Channels have to be listed in alphabetical order
+    let mut channel_list = Vec::new();
+    for name in &["B", "G", "R"] {
+        channel_list.extend_from_slice(name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+        // pLinear and three reserved bytes
+        channel_list.extend_from_slice(&[0, 0, 0, 0]);
+        // x and y sampling
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channel_list.push(0);
+
+    let mut window = Vec::new();
+    for value in &[0, 0, image_x as i32 - 1, image_y as i32 - 1] {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+
+    write_attribute(&mut header, "channels", "chlist", &channel_list);
+    write_attribute(&mut header, "compression", "compression", &[NO_COMPRESSION]);
+    write_attribute(&mut header, "dataWindow", "box2i", &window);
+    write_attribute(&mut header, "displayWindow", "box2i", &window);
+    write_attribute(&mut header, "lineOrder", "lineOrder", &[INCREASING_Y]);
+    write_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    output.write_all(&header)?;
+
+    // Every scanline is its own chunk: y coordinate, data size, then each channel
+    let line_size = 3 * 4 * image_x;
+    let chunk_size = 4 + 4 + line_size;
+    let table_size = 8 * image_y;
+    for y in 0..image_y {
+        let offset = header.len() + table_size + y * chunk_size;
+        output.write_all(&(offset as u64).to_le_bytes())?;
+    }
+
+    let channels: [fn(&Vec3) -> f64; 3] = [Vec3::b, Vec3::g, Vec3::r];
+    for (y, row) in pixels.chunks(image_x).enumerate() {
+        output.write_all(&(y as i32).to_le_bytes())?;
+        output.write_all(&(line_size as i32).to_le_bytes())?;
+
+        for channel in &channels {
+            for pixel in row {
+                output.write_all(&(channel(pixel) as f32).to_le_bytes())?;
+            }
+        }
+    }
+
+    output.flush()
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    /// Reads a null terminated string from the front of `bytes`.
+    fn read_string(bytes: &mut &[u8]) -> String {
+        let end = bytes.iter().position(|&byte| byte == 0).unwrap();
+        let string = String::from_utf8(bytes[..end].to_vec()).unwrap();
+        *bytes = &bytes[end + 1..];
+        string
+    }
+
+    fn read_i32(bytes: &mut &[u8]) -> i32 {
+        let value = i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        *bytes = &bytes[4..];
+        value
+    }
+
+    fn read_f32(bytes: &mut &[u8]) -> f32 {
+        f32::from_bits(read_i32(bytes) as u32)
+    }
+
+    #[test]
+    fn writes_the_header_and_scanlines() {
+        let path = env::temp_dir().join("ray_tracer_exr_layout.exr");
+        let pixels = [
+            Vec3::new(0.25, 0.5, 1.0),
+            Vec3::new(2.0, 4.0, 8.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.5, -1.0, 100.0),
+            Vec3::new(3.0, 2.0, 1.0),
+            Vec3::new(0.125, 0.0, 0.5),
+        ];
+        create(&path, 3, 2, &pixels).unwrap();
+        let file = fs::read(&path).unwrap();
+
+        assert_eq!(&file[..4], &MAGIC);
+        assert_eq!(&file[4..8], &VERSION);
+
+        let mut bytes = &file[8..];
+        let mut attributes = Vec::new();
+        loop {
+            let name = read_string(&mut bytes);
+            if name.is_empty() {
+                break;
+            }
+            let kind = read_string(&mut bytes);
+            let size = read_i32(&mut bytes) as usize;
+            attributes.push((name, kind, bytes[..size].to_vec()));
+            bytes = &bytes[size..];
+        }
+
+        let names: Vec<_> = attributes
+            .iter()
+            .map(|(name, kind, _)| (name.as_str(), kind.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("channels", "chlist"),
+                ("compression", "compression"),
+                ("dataWindow", "box2i"),
+                ("displayWindow", "box2i"),
+                ("lineOrder", "lineOrder"),
+                ("pixelAspectRatio", "float"),
+                ("screenWindowCenter", "v2f"),
+                ("screenWindowWidth", "float"),
+            ]
+        );
+
+        let mut channels = &attributes[0].2[..];
+        for name in &["B", "G", "R"] {
+            assert_eq!(read_string(&mut channels), *name);
+            assert_eq!(read_i32(&mut channels), PIXEL_TYPE_FLOAT);
+            assert_eq!(read_i32(&mut channels), 0);
+            assert_eq!((read_i32(&mut channels), read_i32(&mut channels)), (1, 1));
+        }
+        assert_eq!(channels, &[0]);
+
+        let mut window = &attributes[2].2[..];
+        let window: Vec<_> = (0..4).map(|_| read_i32(&mut window)).collect();
+        assert_eq!(window, vec![0, 0, 2, 1]);
+
+        // The offset table points at each scanline in turn
+        let header_size = file.len() - bytes.len();
+        let offsets: Vec<_> = bytes
+            .chunks(8)
+            .take(2)
+            .map(|offset| {
+                let mut word = [0; 8];
+                word.copy_from_slice(offset);
+                u64::from_le_bytes(word) as usize
+            })
+            .collect();
+        let chunk_size = 4 + 4 + 3 * 4 * 3;
+        assert_eq!(
+            offsets,
+            vec![header_size + 16, header_size + 16 + chunk_size]
+        );
+        assert_eq!(file.len(), offsets[1] + chunk_size);
+
+        for (y, &offset) in offsets.iter().enumerate() {
+            let mut chunk = &file[offset..];
+            assert_eq!(read_i32(&mut chunk), y as i32);
+            assert_eq!(read_i32(&mut chunk), 3 * 4 * 3);
+
+            let row = &pixels[3 * y..3 * y + 3];
+            let channels: [fn(&Vec3) -> f64; 3] = [Vec3::b, Vec3::g, Vec3::r];
+            for channel in &channels {
+                for pixel in row {
+                    assert_eq!(read_f32(&mut chunk), channel(pixel) as f32);
+                }
+            }
+        }
+    }
+}
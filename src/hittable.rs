@@ -2,15 +2,35 @@ use rand::Rng;
 
 use crate::{
     aabb::AABB,
+    animation::{self, Curve},
+    bvh::BoundingVolumeHierarchy,
+    density::DensityField,
+    error::{self, Error},
+    light::Light,
     material::{Material, Scatterable},
     ray::Ray,
+    transform::Transform,
+    util::RandomDouble,
     vec3::Vec3,
 };
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 
 pub trait Hittable<'a, T: Rng>: Sync {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_, T>>;
+    /// `rng` is the renderer's, for participating media to pick where they scatter.
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>>;
     fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AABB>;
+
+    /// Like `hit`, but passes through participating media, for finding what a
+    /// shadow ray reaches.
+    fn surface_hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        self.hit(ray, t_min, t_max, rng)
+    }
+
+    /// Fraction of light that makes it through the media along `ray` between
+    /// `t_min` and `t_max`. Surfaces are left to `surface_hit`.
+    fn transmittance(&self, _ray: &Ray, _t_min: f64, _t_max: f64, _rng: &mut T) -> f64 {
+        1.0
+    }
 }
 
 pub struct Hit<'a, T: Rng> {
@@ -78,6 +98,12 @@ impl<T: Rng> Hit<'_, T> {
     pub fn material(&self) -> &'_ dyn Scatterable<T> {
         self.material
     }
+
+    fn into_world(mut self, transform: &Transform) -> Self {
+        self.p = transform.point_to_world(&self.p);
+        self.normal = transform.normal_to_world(&self.normal);
+        self
+    }
 }
 
 pub trait Center {
@@ -102,7 +128,7 @@ impl Sphere {
 }
 
 impl<'a, T: Rng> Hittable<'a, T> for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_, T>> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, _rng: &mut T) -> Option<Hit<'_, T>> {
         let oc = ray.origin() - &self.center;
 
         let a = ray.direction().dot(ray.direction());
@@ -183,7 +209,7 @@ impl Center for XyRect {
 }
 
 impl<'a, T: Rng> Hittable<'a, T> for XyRect {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_, T>> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, _rng: &mut T) -> Option<Hit<'_, T>> {
         let t = (self.k - ray.origin().z()) / ray.direction().z();
         if t < t_min || t > t_max {
             return None;
@@ -247,7 +273,7 @@ impl Center for XzRect {
 }
 
 impl<'a, T: Rng> Hittable<'a, T> for XzRect {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_, T>> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, _rng: &mut T) -> Option<Hit<'_, T>> {
         let t = (self.k - ray.origin().y()) / ray.direction().y();
         if t < t_min || t > t_max {
             return None;
@@ -311,7 +337,7 @@ impl Center for YzRect {
 }
 
 impl<'a, T: Rng> Hittable<'a, T> for YzRect {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_, T>> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, _rng: &mut T) -> Option<Hit<'_, T>> {
         let t = (self.k - ray.origin().x()) / ray.direction().x();
         if t < t_min || t > t_max {
             return None;
@@ -367,11 +393,11 @@ impl Rect {
 }
 
 impl<'a, T: Rng> Hittable<'a, T> for Rect {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_, T>> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
         match self {
-            Rect::Xy(rect) => rect.hit(ray, t_min, t_max),
-            Rect::Xz(rect) => rect.hit(ray, t_min, t_max),
-            Rect::Yz(rect) => rect.hit(ray, t_min, t_max),
+            Rect::Xy(rect) => rect.hit(ray, t_min, t_max, rng),
+            Rect::Xz(rect) => rect.hit(ray, t_min, t_max, rng),
+            Rect::Yz(rect) => rect.hit(ray, t_min, t_max, rng),
         }
     }
 
@@ -430,12 +456,12 @@ impl Center for Cube {
 }
 
 impl<'a, T: Rng> Hittable<'a, T> for Cube {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_, T>> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
         let mut min_distance = t_max;
         let mut nearest_hit = None;
 
         for hittable in self.sides.iter() {
-            if let Some(hit) = hittable.hit(ray, t_min, min_distance) {
+            if let Some(hit) = hittable.hit(ray, t_min, min_distance, rng) {
                 min_distance = hit.t();
                 nearest_hit = Some(hit);
             }
@@ -465,98 +491,673 @@ impl<'a, T: Rng> Hittable<'a, T> for Cube {
     }
 }
 
+/// Where a moving shape is at `time`, relative to where it was built.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keyframe {
+    time: f64,
+    translate: Vec3,
+    /// About X, Y and Z in degrees.
+    rotate: Vec3,
+    curve: Curve,
+}
+
+impl Keyframe {
+    /// Moves linearly to the next keyframe.
+    pub fn new(time: f64, translate: Vec3, rotate: Vec3) -> Self {
+        Self {
+            time,
+            translate,
+            rotate,
+            curve: Curve::Linear,
+        }
+    }
+
+    /// Eases the way to the next keyframe along `curve`, which shouldn't overshoot
+    /// or the motion blur bounds won't hold.
+    pub fn curve(mut self, curve: Curve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn translate(&self) -> &Vec3 {
+        &self.translate
+    }
+
+    pub fn rotate(&self) -> &Vec3 {
+        &self.rotate
+    }
+}
+
+/// Steps each keyframe interval is split into when bounding the swept motion.
+const MOTION_BOUND_STEPS: usize = 8;
+
+/// Moves and spins any shape about `pivot` over time, easing between keyframes
+/// along each one's `Curve` and holding still before the first and after the last.
+/// Curves never leave 0-1, so the translation stays on the line between keyframes
+/// and the rotation between their angles, which the bounds rely on.
 #[derive(Debug)]
-pub struct Moving<T: Center + Debug> {
-    object: T,
-    center_final: Vec3,
-    time_start: f64,
-    time_end: f64,
+pub struct Moving {
+    shape: Box<Shape>,
+    keyframes: Vec<Keyframe>,
+    pivot: Vec3,
 }
 
-impl<T: Center + Debug> Moving<T> {
-    fn new(object: T, center_final: Vec3, time_start: f64, time_end: f64) -> Self {
+impl Moving {
+    fn new(shape: Shape, mut keyframes: Vec<Keyframe>, pivot: Vec3) -> Self {
+        // Without keyframes the shape just stays where it is
+        if keyframes.is_empty() {
+            keyframes.push(Keyframe::new(0.0, Vec3::default(), Vec3::default()));
+        }
+        keyframes.sort_by(|a, b| a.time.partial_cmp(&b.time).expect("keyframe time is NaN"));
+
         Self {
-            object,
-            center_final,
-            time_start,
-            time_end,
+            shape: Box::new(shape),
+            keyframes,
+            pivot,
         }
     }
+
+    /// Translation and rotation at `time`.
+    fn pose(&self, time: f64) -> (Vec3, Vec3) {
+        let (from, to, f) = animation::locate(&self.keyframes, time, |keyframe| keyframe.time);
+        let (from, to) = (&self.keyframes[from], &self.keyframes[to]);
+
+        let f = from.curve.ease(f);
+        let lerp = |a: &Vec3, b: &Vec3| a + f * (b - a);
+
+        (
+            lerp(&from.translate, &to.translate),
+            lerp(&from.rotate, &to.rotate),
+        )
+    }
+
+    fn transform(&self, time: f64) -> Transform {
+        let (translate, rotate) = self.pose(time);
+        Transform::rigid(&translate, &rotate, &self.pivot)
+    }
 }
 
-impl<'a, T: Rng> Hittable<'a, T> for Moving<Sphere> {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_, T>> {
-        let radius = self.object.radius;
-        let oc = ray.origin() - &self.center(ray.time());
+impl<'a, T: Rng> Hittable<'a, T> for Moving {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        let transform = self.transform(ray.time());
 
-        let a = ray.direction().dot(ray.direction());
-        let b = oc.dot(ray.direction());
-        let c = oc.dot(&oc) - radius * radius;
+        let local_ray = transform.ray_to_local(ray);
+        let hit: Hit<'_, T> = self.shape.hit(&local_ray, t_min, t_max, rng)?;
 
-        let discriminant = b * b - a * c;
-        if discriminant < 0.0 {
+        Some(hit.into_world(&transform))
+    }
+
+    fn surface_hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        let transform = self.transform(ray.time());
+
+        let local_ray = transform.ray_to_local(ray);
+        let hit: Hit<'_, T> = self.shape.surface_hit(&local_ray, t_min, t_max, rng)?;
+
+        Some(hit.into_world(&transform))
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> f64 {
+        let local_ray = self.transform(ray.time()).ray_to_local(ray);
+        let shape: &dyn Hittable<'a, T> = self.shape.as_ref();
+        shape.transmittance(&local_ray, t_min, t_max, rng)
+    }
+
+    fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AABB> {
+        let shape: &dyn Hittable<'a, T> = self.shape.as_ref();
+        let aabb = shape.bounding_box(time_start, time_end)?;
+
+        // Easing only changes how fast the shape moves along the straight line
+        // between keyframes, never overshooting either end, so sampling at each of
+        // them and in between bounds the translation exactly
+        let mut times = vec![time_start, time_end];
+        times.extend(
+            self.keyframes
+                .iter()
+                .map(|keyframe| keyframe.time)
+                .filter(|&time| time > time_start && time < time_end),
+        );
+        times.sort_by(f64::total_cmp);
+
+        // Rotating between samples bows points out from the straight line joining
+        // them by at most r·θ²/8, where r is their distance from the pivot and θ
+        // the sum of the turns about each axis. That line runs between the boxes
+        // at either end of the step, so both are padded.
+        let radius = (0..8)
+            .map(|corner| {
+                let x = if corner & 1 == 0 {
+                    aabb.min().x()
+                } else {
+                    aabb.max().x()
+                };
+                let y = if corner & 2 == 0 {
+                    aabb.min().y()
+                } else {
+                    aabb.max().y()
+                };
+                let z = if corner & 4 == 0 {
+                    aabb.min().z()
+                } else {
+                    aabb.max().z()
+                };
+
+                (Vec3::new(x, y, z) - &self.pivot).length()
+            })
+            .fold(0.0, f64::max);
+
+        let mut swept = self.transform(time_start).bounding_box_to_world(&aabb);
+        let mut previous_box = swept.clone();
+        for pair in times.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+
+            for step in 1..=MOTION_BOUND_STEPS {
+                let previous =
+                    start + (end - start) * (step - 1) as f64 / MOTION_BOUND_STEPS as f64;
+                let time = start + (end - start) * step as f64 / MOTION_BOUND_STEPS as f64;
+
+                let turn = &self.pose(time).1 - &self.pose(previous).1;
+                let theta = (turn.x().abs() + turn.y().abs() + turn.z().abs()).to_radians();
+                let bow = radius * theta * theta / 8.0;
+                let padding = Vec3::new(bow, bow, bow);
+
+                let step_box = self.transform(time).bounding_box_to_world(&aabb);
+                let both = previous_box.surrounding_box(&step_box);
+                let padded = AABB::new(both.min() - &padding, both.max() + &padding);
+                swept = swept.surrounding_box(&padded);
+                previous_box = step_box;
+            }
+        }
+
+        Some(swept)
+    }
+}
+
+/// Smoke or fog of even density filling a closed `boundary`, which scatters with
+/// the boundary's material. Rays travel a random distance inside before
+/// scattering, and pass straight through if that's further than the far side.
+#[derive(Debug)]
+pub struct ConstantMedium {
+    boundary: Box<Shape>,
+    density: f64,
+}
+
+impl ConstantMedium {
+    fn new(boundary: Shape, density: f64) -> Self {
+        Self {
+            boundary: Box::new(boundary),
+            density,
+        }
+    }
+}
+
+impl<'a, T: Rng> Hittable<'a, T> for ConstantMedium {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        let (t_entry, t_exit, material) = medium_span(&self.boundary, ray, t_min, t_max, rng)?;
+
+        let ray_length = ray.direction().length();
+        let distance_inside = (t_exit - t_entry) * ray_length;
+        let hit_distance = -(1.0 - rng.random_double()).ln() / self.density;
+        if hit_distance > distance_inside {
             return None;
         }
-        let discriminant = discriminant.sqrt();
 
-        let f = (-b - discriminant) / a;
-        let g = (-b + discriminant) / a;
+        let t = t_entry + hit_distance / ray_length;
+        Some(medium_hit(ray, t, material))
+    }
 
-        let t = if f < t_max && f > t_min {
-            f
-        } else if g < t_max && g > t_min {
-            g
-        } else {
+    fn surface_hit(
+        &self,
+        _ray: &Ray,
+        _t_min: f64,
+        _t_max: f64,
+        _rng: &mut T,
+    ) -> Option<Hit<'_, T>> {
+        None
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> f64 {
+        match medium_span::<T>(&self.boundary, ray, t_min, t_max, rng) {
+            Some((t_entry, t_exit, _)) => {
+                let distance_inside = (t_exit - t_entry) * ray.direction().length();
+                (-self.density * distance_inside).exp()
+            }
+            None => 1.0,
+        }
+    }
+
+    fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AABB> {
+        let boundary: &dyn Hittable<'a, T> = self.boundary.as_ref();
+        boundary.bounding_box(time_start, time_end)
+    }
+}
+
+/// Smoke or clouds filling a closed `boundary`, thickest where `field` is, up to
+/// `density` where it reaches 1. Free flights are sampled by delta tracking and
+/// shadow rays dimmed by ratio tracking, both against the densest the field gets.
+#[derive(Debug)]
+pub struct HeterogeneousMedium {
+    boundary: Box<Shape>,
+    field: DensityField,
+    density: f64,
+}
+
+impl HeterogeneousMedium {
+    fn new(boundary: Shape, field: DensityField, density: f64) -> Self {
+        Self {
+            boundary: Box::new(boundary),
+            field,
+            density,
+        }
+    }
+
+    fn density_at(&self, p: &Vec3) -> f64 {
+        self.density * self.field.value(p)
+    }
+
+    fn max_density(&self) -> f64 {
+        self.density * self.field.max()
+    }
+}
+
+impl<'a, T: Rng> Hittable<'a, T> for HeterogeneousMedium {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        let (t_entry, t_exit, material) = medium_span(&self.boundary, ray, t_min, t_max, rng)?;
+
+        let max_density = self.max_density();
+        if max_density <= 0.0 {
             return None;
+        }
+
+        // Step through as if the medium were as dense as it gets everywhere, and
+        // only scatter at the fraction of collisions the real density accounts for
+        let step = 1.0 / (max_density * ray.direction().length());
+        let mut t = t_entry;
+        loop {
+            t -= (1.0 - rng.random_double()).ln() * step;
+            if t >= t_exit {
+                return None;
+            }
+
+            if rng.random_double() * max_density < self.density_at(&ray.point_at(t)) {
+                return Some(medium_hit(ray, t, material));
+            }
+        }
+    }
+
+    fn surface_hit(
+        &self,
+        _ray: &Ray,
+        _t_min: f64,
+        _t_max: f64,
+        _rng: &mut T,
+    ) -> Option<Hit<'_, T>> {
+        None
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> f64 {
+        let (t_entry, t_exit) = match medium_span::<T>(&self.boundary, ray, t_min, t_max, rng) {
+            Some((t_entry, t_exit, _)) => (t_entry, t_exit),
+            None => return 1.0,
         };
 
-        let p = ray.point_at(t);
-        let outward_normal = (&p - &self.center(ray.time())) / radius;
-        let material = &self.object.material;
+        let max_density = self.max_density();
+        if max_density <= 0.0 {
+            return 1.0;
+        }
 
-        let (u, v) = sphere_uv(&outward_normal);
-        let hit = Hit::new(t, u, v, p, ray, outward_normal, material);
+        // Rather than stopping at the first collision, each one lets through the
+        // fraction of light the real density there doesn't scatter
+        let step = 1.0 / (max_density * ray.direction().length());
+        let mut transmittance = 1.0;
+        let mut t = t_entry;
+        loop {
+            t -= (1.0 - rng.random_double()).ln() * step;
+            if t >= t_exit || transmittance <= 0.0 {
+                return transmittance;
+            }
 
-        Some(hit)
+            transmittance *= 1.0 - self.density_at(&ray.point_at(t)) / max_density;
+        }
     }
 
     fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AABB> {
-        let f = |center: &Vec3| {
-            let radius = self.object.radius;
+        let boundary: &dyn Hittable<'a, T> = self.boundary.as_ref();
+        boundary.bounding_box(time_start, time_end)
+    }
+}
 
-            let min = center - Vec3::new(radius, radius, radius);
-            let max = center + Vec3::new(radius, radius, radius);
+/// Where along `ray` it's inside `boundary`, within `t_min` and `t_max`, and the
+/// material the medium scatters with.
+fn medium_span<'a, T: Rng>(
+    boundary: &'a Shape,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+    rng: &mut T,
+) -> Option<(f64, f64, &'a dyn Scatterable<T>)> {
+    // Find where the ray is inside the boundary, even if it started in there
+    let entry: Hit<'_, T> = boundary.hit(ray, f64::NEG_INFINITY, f64::INFINITY, rng)?;
+    let exit: Hit<'_, T> = boundary.hit(ray, entry.t + 0.0001, f64::INFINITY, rng)?;
+
+    let t_entry = entry.t.max(t_min);
+    let t_exit = exit.t.min(t_max);
+    if t_entry >= t_exit {
+        return None;
+    }
 
-            AABB::new(min, max)
-        };
+    Some((t_entry, t_exit, entry.material))
+}
+
+fn medium_hit<'a, T: Rng>(ray: &Ray, t: f64, material: &'a dyn Scatterable<T>) -> Hit<'a, T> {
+    // Scattering in a volume doesn't depend on the normal, so any will do
+    let normal = Vec3::new(1.0, 0.0, 0.0);
+    Hit::new(t, 0.0, 0.0, ray.point_at(t), ray, normal, material)
+}
 
-        let center_initial = self.center(time_start);
-        let center_final = self.center(time_end);
+#[derive(Debug)]
+pub struct Triangle {
+    vertices: [Vec3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    material: Material,
+}
 
-        if center_initial == center_final {
-            let aabb = f(&center_initial);
-            return Some(aabb);
+impl Triangle {
+    pub fn new(
+        vertices: [Vec3; 3],
+        normals: Option<[Vec3; 3]>,
+        uvs: Option<[(f64, f64); 3]>,
+        material: Material,
+    ) -> Self {
+        Self {
+            vertices,
+            normals,
+            uvs,
+            material,
         }
+    }
+}
+
+impl<'a, T: Rng> Hittable<'a, T> for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, _rng: &mut T) -> Option<Hit<'_, T>> {
+        let [p0, p1, p2] = &self.vertices;
+        let normals = self.normals.as_ref().map(|[n0, n1, n2]| [n0, n1, n2]);
+        let uvs = self.uvs.as_ref().map(|[uv0, uv1, uv2]| [uv0, uv1, uv2]);
+
+        triangle_hit(
+            ray,
+            t_min,
+            t_max,
+            [p0, p1, p2],
+            normals,
+            uvs,
+            &self.material,
+        )
+    }
+
+    fn bounding_box(&self, _time_start: f64, _time_end: f64) -> Option<AABB> {
+        let [p0, p1, p2] = &self.vertices;
+        Some(triangle_bounding_box([p0, p1, p2]))
+    }
+}
+
+/// Indexes into the vertex attribute lists of a `Mesh` for a single triangle.
+#[derive(Clone, Debug)]
+pub struct Face {
+    positions: [usize; 3],
+    normals: Option<[usize; 3]>,
+    uvs: Option<[usize; 3]>,
+    material: usize,
+}
 
-        let initial_box = f(&center_initial);
-        let final_box = f(&center_final);
+impl Face {
+    pub fn new(
+        positions: [usize; 3],
+        normals: Option<[usize; 3]>,
+        uvs: Option<[usize; 3]>,
+        material: usize,
+    ) -> Self {
+        Self {
+            positions,
+            normals,
+            uvs,
+            material,
+        }
+    }
 
-        let surrounding_box = initial_box.surrounding_box(&final_box);
+    pub fn positions(&self) -> [usize; 3] {
+        self.positions
+    }
 
-        Some(surrounding_box)
+    pub fn normals(&self) -> Option<[usize; 3]> {
+        self.normals
     }
+
+    pub fn uvs(&self) -> Option<[usize; 3]> {
+        self.uvs
+    }
+
+    pub fn material(&self) -> usize {
+        self.material
+    }
+}
+
+/// Triangle mesh with shared vertex attributes.
+///
+/// A mesh is not a `Shape` on its own; use `Shape::mesh` to split it into one
+/// `MeshTriangle` per face so the faces end up in the world's BVH.
+#[derive(Debug)]
+pub struct Mesh {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<Face>,
+    materials: Vec<Material>,
 }
 
-impl<T: Center + Debug> Center for Moving<T> {
-    fn center(&self, time: f64) -> Vec3 {
-        // Always assume t=0 for initial center
-        let center_initial = &self.object.center(0.0);
+impl Mesh {
+    /// Fails if a face refers to a vertex attribute or material that isn't there.
+    pub fn new(
+        positions: Vec<Vec3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<Face>,
+        materials: Vec<Material>,
+    ) -> error::Result<Self> {
+        for face in faces.iter() {
+            let in_range = face.positions.iter().all(|&i| i < positions.len())
+                && face.normals.iter().flatten().all(|&i| i < normals.len())
+                && face.uvs.iter().flatten().all(|&i| i < uvs.len())
+                && face.material < materials.len();
+
+            if !in_range {
+                let message = format!(
+                    "Mesh face {:?} is out of range for {} positions, {} normals, {} uvs \
+                     and {} materials",
+                    face,
+                    positions.len(),
+                    normals.len(),
+                    uvs.len(),
+                    materials.len()
+                );
+                return Err(Error::scene(message));
+            }
+        }
 
-        let elapsed_time = (time - self.time_start) as f64;
-        let movement_time = (self.time_end - self.time_start) as f64;
+        Ok(Self {
+            positions,
+            normals,
+            uvs,
+            faces,
+            materials,
+        })
+    }
 
-        let distance = &self.center_final - center_initial;
-        center_initial + ((elapsed_time / movement_time) * &distance)
+    pub fn faces(&self) -> &[Face] {
+        &self.faces
+    }
+
+    /// Bakes `transform` into the vertex data, which is cheaper than wrapping every
+    /// triangle in a `Transformed`.
+    pub fn transform(&mut self, transform: &Transform) {
+        for position in self.positions.iter_mut() {
+            *position = transform.point_to_world(position);
+        }
+
+        for normal in self.normals.iter_mut() {
+            *normal = transform.normal_to_world(normal);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MeshTriangle {
+    mesh: Arc<Mesh>,
+    face: usize,
+}
+
+impl MeshTriangle {
+    fn new(mesh: Arc<Mesh>, face: usize) -> Self {
+        Self { mesh, face }
+    }
+
+    fn positions(&self) -> [&Vec3; 3] {
+        let [i0, i1, i2] = self.mesh.faces[self.face].positions;
+        let positions = &self.mesh.positions;
+
+        [&positions[i0], &positions[i1], &positions[i2]]
+    }
+}
+
+impl<'a, T: Rng> Hittable<'a, T> for MeshTriangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, _rng: &mut T) -> Option<Hit<'_, T>> {
+        let mesh = &self.mesh;
+        let face = &mesh.faces[self.face];
+
+        let normals = face
+            .normals
+            .map(|[i0, i1, i2]| [&mesh.normals[i0], &mesh.normals[i1], &mesh.normals[i2]]);
+        let uvs = face
+            .uvs
+            .map(|[i0, i1, i2]| [&mesh.uvs[i0], &mesh.uvs[i1], &mesh.uvs[i2]]);
+        let material = &mesh.materials[face.material];
+
+        triangle_hit(ray, t_min, t_max, self.positions(), normals, uvs, material)
+    }
+
+    fn bounding_box(&self, _time_start: f64, _time_end: f64) -> Option<AABB> {
+        Some(triangle_bounding_box(self.positions()))
+    }
+}
+
+/// Places any shape in the world through an affine transform.
+#[derive(Debug)]
+pub struct Transformed {
+    shape: Box<Shape>,
+    transform: Transform,
+}
+
+impl Transformed {
+    fn new(shape: Shape, transform: Transform) -> Self {
+        Self {
+            shape: Box::new(shape),
+            transform,
+        }
+    }
+}
+
+impl<'a, T: Rng> Hittable<'a, T> for Transformed {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        let local_ray = self.transform.ray_to_local(ray);
+        let hit: Hit<'_, T> = self.shape.hit(&local_ray, t_min, t_max, rng)?;
+
+        Some(hit.into_world(&self.transform))
+    }
+
+    fn surface_hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        let local_ray = self.transform.ray_to_local(ray);
+        let hit: Hit<'_, T> = self.shape.surface_hit(&local_ray, t_min, t_max, rng)?;
+
+        Some(hit.into_world(&self.transform))
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> f64 {
+        let local_ray = self.transform.ray_to_local(ray);
+        let shape: &dyn Hittable<'a, T> = self.shape.as_ref();
+        shape.transmittance(&local_ray, t_min, t_max, rng)
+    }
+
+    fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AABB> {
+        let shape: &dyn Hittable<'a, T> = self.shape.as_ref();
+        let aabb = shape.bounding_box(time_start, time_end)?;
+
+        Some(self.transform.bounding_box_to_world(&aabb))
+    }
+}
+
+/// Places a shared prototype in the world, optionally overriding the material of
+/// everything in it.
+///
+/// The prototype keeps its own BVH, so the world BVH only has to bound the
+/// instance and the prototype geometry is stored once however often it's used.
+#[derive(Debug)]
+pub struct Instance {
+    prototype: Arc<BoundingVolumeHierarchy>,
+    transform: Transform,
+    material: Option<Material>,
+}
+
+impl Instance {
+    fn new(
+        prototype: Arc<BoundingVolumeHierarchy>,
+        transform: Transform,
+        material: Option<Material>,
+    ) -> Self {
+        Self {
+            prototype,
+            transform,
+            material,
+        }
+    }
+}
+
+impl<'a, T: Rng> Hittable<'a, T> for Instance {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        let local_ray = self.transform.ray_to_local(ray);
+        let mut hit: Hit<'_, T> = self.prototype.hit(&local_ray, t_min, t_max, rng)?;
+
+        if let Some(material) = &self.material {
+            hit.material = material;
+        }
+
+        Some(hit.into_world(&self.transform))
+    }
+
+    fn surface_hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        let local_ray = self.transform.ray_to_local(ray);
+        let mut hit: Hit<'_, T> = self.prototype.surface_hit(&local_ray, t_min, t_max, rng)?;
+
+        if let Some(material) = &self.material {
+            hit.material = material;
+        }
+
+        Some(hit.into_world(&self.transform))
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> f64 {
+        let local_ray = self.transform.ray_to_local(ray);
+        let prototype: &dyn Hittable<'a, T> = self.prototype.as_ref();
+        prototype.transmittance(&local_ray, t_min, t_max, rng)
+    }
+
+    fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AABB> {
+        let prototype: &dyn Hittable<'a, T> = self.prototype.as_ref();
+        let aabb = prototype.bounding_box(time_start, time_end)?;
+
+        Some(self.transform.bounding_box_to_world(&aabb))
     }
 }
 
@@ -566,8 +1167,14 @@ pub enum Shape {
     XyRect(XyRect),
     XzRect(XzRect),
     YzRect(YzRect),
-    Cube(Cube),
-    MovingSphere(Moving<Sphere>),
+    Cube(Box<Cube>),
+    Moving(Box<Moving>),
+    ConstantMedium(Box<ConstantMedium>),
+    HeterogeneousMedium(Box<HeterogeneousMedium>),
+    Triangle(Triangle),
+    MeshTriangle(MeshTriangle),
+    Transformed(Transformed),
+    Instance(Instance),
 }
 
 impl Shape {
@@ -597,7 +1204,7 @@ impl Shape {
         let p1 = Vec3::new(x1, y1, z1);
 
         let cube = Cube::new(p0, p1, material);
-        Self::Cube(cube)
+        Self::Cube(Box::new(cube))
     }
 
     pub fn moving_sphere(
@@ -614,23 +1221,123 @@ impl Shape {
     ) -> Self {
         let center_initial = Vec3::new(x0, y0, z0);
         let center_final = Vec3::new(x1, y1, z1);
-        let sphere: Sphere = Sphere::new(center_initial, radius, material);
+        let sphere = Shape::Sphere(Sphere::new(center_initial.clone(), radius, material));
+
+        let keyframes = vec![
+            Keyframe::new(time_start, Vec3::default(), Vec3::default()),
+            Keyframe::new(time_end, center_final - &center_initial, Vec3::default()),
+        ];
+
+        Self::moving(sphere, keyframes, center_initial)
+    }
 
-        let sphere = Moving::new(sphere, center_final, time_start, time_end);
+    /// Rotations in the keyframes are about `pivot`.
+    pub fn moving(shape: Shape, keyframes: Vec<Keyframe>, pivot: Vec3) -> Self {
+        let moving = Moving::new(shape, keyframes, pivot);
+        Self::Moving(Box::new(moving))
+    }
+
+    /// Fills `boundary`, which has to be closed, with a medium scattering with the
+    /// boundary's material, usually an isotropic one.
+    pub fn constant_medium(boundary: Shape, density: f64) -> Self {
+        let medium = ConstantMedium::new(boundary, density);
+        Self::ConstantMedium(Box::new(medium))
+    }
+
+    /// Fills `boundary` like `constant_medium`, but only as thick as `field` says,
+    /// up to `density`.
+    pub fn heterogeneous_medium(boundary: Shape, field: DensityField, density: f64) -> Self {
+        let medium = HeterogeneousMedium::new(boundary, field, density);
+        Self::HeterogeneousMedium(Box::new(medium))
+    }
 
-        Self::MovingSphere(sphere)
+    pub fn triangle(v0: Vec3, v1: Vec3, v2: Vec3, material: Material) -> Self {
+        let triangle = Triangle::new([v0, v1, v2], None, None, material);
+        Self::Triangle(triangle)
+    }
+
+    pub fn transformed(shape: Shape, transform: Transform) -> Self {
+        let transformed = Transformed::new(shape, transform);
+        Self::Transformed(transformed)
+    }
+
+    pub fn instance(
+        prototype: Arc<BoundingVolumeHierarchy>,
+        transform: Transform,
+        material: Option<Material>,
+    ) -> Self {
+        let instance = Instance::new(prototype, transform, material);
+        Self::Instance(instance)
+    }
+
+    /// Whether there's a participating medium in here, which shadow rays have to
+    /// look for.
+    pub fn has_media(&self) -> bool {
+        match self {
+            Shape::ConstantMedium(_) | Shape::HeterogeneousMedium(_) => true,
+            Shape::Moving(moving) => moving.shape.has_media(),
+            Shape::Transformed(transformed) => transformed.shape.has_media(),
+            Shape::Instance(instance) => instance.prototype.has_media(),
+            _ => false,
+        }
+    }
+
+    /// The shape's geometry as a `Light` if it's emissive and can be sampled directly.
+    pub fn light(&self) -> Option<Light> {
+        match self {
+            Shape::Sphere(sphere) if sphere.material.is_emissive() => Some(Light::Sphere {
+                center: sphere.center.clone(),
+                radius: sphere.radius.abs(),
+            }),
+            Shape::XyRect(rect) if rect.material.is_emissive() => Some(Light::XyRect {
+                x0: rect.x0,
+                x1: rect.x1,
+                y0: rect.y0,
+                y1: rect.y1,
+                k: rect.k,
+            }),
+            Shape::XzRect(rect) if rect.material.is_emissive() => Some(Light::XzRect {
+                x0: rect.x0,
+                x1: rect.x1,
+                z0: rect.z0,
+                z1: rect.z1,
+                k: rect.k,
+            }),
+            Shape::YzRect(rect) if rect.material.is_emissive() => Some(Light::YzRect {
+                y0: rect.y0,
+                y1: rect.y1,
+                z0: rect.z0,
+                z1: rect.z1,
+                k: rect.k,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn mesh(mesh: Mesh) -> Vec<Self> {
+        let mesh = Arc::new(mesh);
+
+        (0..mesh.faces().len())
+            .map(|face| Self::MeshTriangle(MeshTriangle::new(mesh.clone(), face)))
+            .collect()
     }
 }
 
 impl<'a, T: Rng> Hittable<'a, T> for Shape {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_, T>> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
         match self {
-            Shape::Sphere(sphere) => sphere.hit(ray, t_min, t_max),
-            Shape::XyRect(rect) => rect.hit(ray, t_min, t_max),
-            Shape::XzRect(rect) => rect.hit(ray, t_min, t_max),
-            Shape::YzRect(rect) => rect.hit(ray, t_min, t_max),
-            Shape::Cube(cube) => cube.hit(ray, t_min, t_max),
-            Shape::MovingSphere(sphere) => sphere.hit(ray, t_min, t_max),
+            Shape::Sphere(sphere) => sphere.hit(ray, t_min, t_max, rng),
+            Shape::XyRect(rect) => rect.hit(ray, t_min, t_max, rng),
+            Shape::XzRect(rect) => rect.hit(ray, t_min, t_max, rng),
+            Shape::YzRect(rect) => rect.hit(ray, t_min, t_max, rng),
+            Shape::Cube(cube) => cube.hit(ray, t_min, t_max, rng),
+            Shape::Moving(moving) => moving.hit(ray, t_min, t_max, rng),
+            Shape::ConstantMedium(medium) => medium.hit(ray, t_min, t_max, rng),
+            Shape::HeterogeneousMedium(medium) => medium.hit(ray, t_min, t_max, rng),
+            Shape::Triangle(triangle) => triangle.hit(ray, t_min, t_max, rng),
+            Shape::MeshTriangle(triangle) => triangle.hit(ray, t_min, t_max, rng),
+            Shape::Transformed(transformed) => transformed.hit(ray, t_min, t_max, rng),
+            Shape::Instance(instance) => instance.hit(ray, t_min, t_max, rng),
         }
     }
 
@@ -653,54 +1360,156 @@ impl<'a, T: Rng> Hittable<'a, T> for Shape {
                 rect.bounding_box(time_start, time_end)
             }
             Shape::Cube(cube) => {
-                let cube: &dyn Hittable<'a, T> = cube;
+                let cube: &dyn Hittable<'a, T> = cube.as_ref();
                 cube.bounding_box(time_start, time_end)
             }
-            Shape::MovingSphere(sphere) => {
-                let sphere: &dyn Hittable<'a, T> = sphere;
-                sphere.bounding_box(time_start, time_end)
+            Shape::Moving(moving) => {
+                let moving: &dyn Hittable<'a, T> = moving.as_ref();
+                moving.bounding_box(time_start, time_end)
+            }
+            Shape::ConstantMedium(medium) => {
+                let medium: &dyn Hittable<'a, T> = medium.as_ref();
+                medium.bounding_box(time_start, time_end)
+            }
+            Shape::HeterogeneousMedium(medium) => {
+                let medium: &dyn Hittable<'a, T> = medium.as_ref();
+                medium.bounding_box(time_start, time_end)
+            }
+            Shape::Triangle(triangle) => {
+                let triangle: &dyn Hittable<'a, T> = triangle;
+                triangle.bounding_box(time_start, time_end)
+            }
+            Shape::MeshTriangle(triangle) => {
+                let triangle: &dyn Hittable<'a, T> = triangle;
+                triangle.bounding_box(time_start, time_end)
+            }
+            Shape::Transformed(transformed) => {
+                let transformed: &dyn Hittable<'a, T> = transformed;
+                transformed.bounding_box(time_start, time_end)
+            }
+            Shape::Instance(instance) => {
+                let instance: &dyn Hittable<'a, T> = instance;
+                instance.bounding_box(time_start, time_end)
             }
         }
     }
+
+    fn surface_hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        match self {
+            Shape::ConstantMedium(_) | Shape::HeterogeneousMedium(_) => None,
+            Shape::Moving(moving) => moving.surface_hit(ray, t_min, t_max, rng),
+            Shape::Transformed(transformed) => transformed.surface_hit(ray, t_min, t_max, rng),
+            Shape::Instance(instance) => instance.surface_hit(ray, t_min, t_max, rng),
+            _ => self.hit(ray, t_min, t_max, rng),
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> f64 {
+        match self {
+            Shape::ConstantMedium(medium) => {
+                let medium: &dyn Hittable<'a, T> = medium.as_ref();
+                medium.transmittance(ray, t_min, t_max, rng)
+            }
+            Shape::HeterogeneousMedium(medium) => {
+                let medium: &dyn Hittable<'a, T> = medium.as_ref();
+                medium.transmittance(ray, t_min, t_max, rng)
+            }
+            Shape::Moving(moving) => {
+                let moving: &dyn Hittable<'a, T> = moving.as_ref();
+                moving.transmittance(ray, t_min, t_max, rng)
+            }
+            Shape::Transformed(transformed) => {
+                let transformed: &dyn Hittable<'a, T> = transformed;
+                transformed.transmittance(ray, t_min, t_max, rng)
+            }
+            Shape::Instance(instance) => {
+                let instance: &dyn Hittable<'a, T> = instance;
+                instance.transmittance(ray, t_min, t_max, rng)
+            }
+            _ => 1.0,
+        }
+    }
 }
 
 impl<'a, T: Rng> Hittable<'a, T> for Vec<Shape> {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_, T>> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
         let shapes = &self[..];
-        hit(shapes, ray, t_min, t_max)
+        nearest_hit(shapes, t_max, |shape, t_max| {
+            shape.hit(ray, t_min, t_max, rng)
+        })
     }
 
     fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AABB> {
         bounding_box::<T>(self, time_start, time_end)
     }
+
+    fn surface_hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        let shapes = &self[..];
+        nearest_hit(shapes, t_max, |shape, t_max| {
+            shape.surface_hit(ray, t_min, t_max, rng)
+        })
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> f64 {
+        transmittance(self, ray, t_min, t_max, rng)
+    }
 }
 
 impl<'a, T: Rng> Hittable<'a, T> for &'a mut [Shape] {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_, T>> {
-        hit(self, ray, t_min, t_max)
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        nearest_hit(self, t_max, |shape, t_max| {
+            shape.hit(ray, t_min, t_max, rng)
+        })
     }
 
     fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AABB> {
         bounding_box::<T>(self, time_start, time_end)
     }
+
+    fn surface_hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        nearest_hit(self, t_max, |shape, t_max| {
+            shape.surface_hit(ray, t_min, t_max, rng)
+        })
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> f64 {
+        transmittance(self, ray, t_min, t_max, rng)
+    }
 }
 
 impl<'a, T: Rng> Hittable<'a, T> for &'a [Shape] {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'_, T>> {
-        hit(self, ray, t_min, t_max)
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        nearest_hit(self, t_max, |shape, t_max| {
+            shape.hit(ray, t_min, t_max, rng)
+        })
     }
 
     fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AABB> {
         bounding_box::<T>(self, time_start, time_end)
     }
+
+    fn surface_hit(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> Option<Hit<'_, T>> {
+        nearest_hit(self, t_max, |shape, t_max| {
+            shape.surface_hit(ray, t_min, t_max, rng)
+        })
+    }
+
+    fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> f64 {
+        transmittance(self, ray, t_min, t_max, rng)
+    }
 }
 
-fn hit<'a, T: Rng>(shapes: &'a [Shape], ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit<'a, T>> {
+/// The closest of the hits `hit` finds on each shape, given the distance to the
+/// closest one so far.
+fn nearest_hit<'a, T: Rng, F>(shapes: &'a [Shape], t_max: f64, mut hit: F) -> Option<Hit<'a, T>>
+where
+    F: FnMut(&'a Shape, f64) -> Option<Hit<'a, T>>,
+{
     let mut min_distance = t_max;
     let mut nearest_hit = None;
 
-    for hittable in shapes.iter() {
-        if let Some(hit) = hittable.hit(ray, t_min, min_distance) {
+    for shape in shapes.iter() {
+        if let Some(hit) = hit(shape, min_distance) {
             min_distance = hit.t();
             nearest_hit = Some(hit);
         }
@@ -709,14 +1518,25 @@ fn hit<'a, T: Rng>(shapes: &'a [Shape], ray: &Ray, t_min: f64, t_max: f64) -> Op
     nearest_hit
 }
 
-fn bounding_box<'a, T: Rng>(shapes: &[Shape], time_start: f64, time_end: f64) -> Option<AABB> {
+fn transmittance<T: Rng>(shapes: &[Shape], ray: &Ray, t_min: f64, t_max: f64, rng: &mut T) -> f64 {
+    shapes
+        .iter()
+        .filter(|shape| shape.has_media())
+        .map(|shape| {
+            let shape: &dyn Hittable<'_, T> = shape;
+            shape.transmittance(ray, t_min, t_max, rng)
+        })
+        .product()
+}
+
+fn bounding_box<T: Rng>(shapes: &[Shape], time_start: f64, time_end: f64) -> Option<AABB> {
     let mut aabb = shapes.first().and_then(|it| {
-        let it: &dyn Hittable<'a, T> = it;
+        let it: &dyn Hittable<'_, T> = it;
         it.bounding_box(time_start, time_end)
     })?;
 
     for shape in shapes.iter().skip(1) {
-        let shape: &dyn Hittable<'a, T> = shape;
+        let shape: &dyn Hittable<'_, T> = shape;
 
         if let Some(new_aabb) = shape.bounding_box(time_start, time_end) {
             aabb = aabb.surrounding_box(&new_aabb);
@@ -729,6 +1549,83 @@ fn bounding_box<'a, T: Rng>(shapes: &[Shape], time_start: f64, time_end: f64) ->
     Some(aabb)
 }
 
+fn triangle_hit<'a, T: Rng>(
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+    positions: [&Vec3; 3],
+    normals: Option<[&Vec3; 3]>,
+    uvs: Option<[&(f64, f64); 3]>,
+    material: &'a Material,
+) -> Option<Hit<'a, T>> {
+    let [p0, p1, p2] = positions;
+
+    // Moller-Trumbore; b1 and b2 are the barycentric weights of p1 and p2
+    let edge_a = p1 - p0;
+    let edge_b = p2 - p0;
+
+    let p_vec = ray.direction().cross(&edge_b);
+    let determinant = edge_a.dot(&p_vec);
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+
+    let t_vec = ray.origin() - p0;
+    let b1 = t_vec.dot(&p_vec) * inverse_determinant;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let q_vec = t_vec.cross(&edge_a);
+    let b2 = ray.direction().dot(&q_vec) * inverse_determinant;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge_b.dot(&q_vec) * inverse_determinant;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    let b0 = 1.0 - b1 - b2;
+    let p = ray.point_at(t);
+
+    let outward_normal = match normals {
+        Some([n0, n1, n2]) => (b0 * n0 + b1 * n1 + b2 * n2).into_unit(),
+        None => edge_a.cross(&edge_b).into_unit(),
+    };
+
+    let (u, v) = match uvs {
+        Some([uv0, uv1, uv2]) => (
+            b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+            b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+        ),
+        None => (b1, b2),
+    };
+
+    let hit = Hit::new(t, u, v, p, ray, outward_normal, material);
+    Some(hit)
+}
+
+fn triangle_bounding_box(positions: [&Vec3; 3]) -> AABB {
+    let [p0, p1, p2] = positions;
+
+    // Pad the box so axis-aligned triangles don't produce a degenerate one
+    let min = Vec3::new(
+        p0.x().min(p1.x()).min(p2.x()) - 0.0001,
+        p0.y().min(p1.y()).min(p2.y()) - 0.0001,
+        p0.z().min(p1.z()).min(p2.z()) - 0.0001,
+    );
+    let max = Vec3::new(
+        p0.x().max(p1.x()).max(p2.x()) + 0.0001,
+        p0.y().max(p1.y()).max(p2.y()) + 0.0001,
+        p0.z().max(p1.z()).max(p2.z()) + 0.0001,
+    );
+
+    AABB::new(min, max)
+}
+
 fn sphere_uv(p: &Vec3) -> (f64, f64) {
     use std::f64::consts::{PI, TAU};
 
@@ -747,3 +1644,104 @@ fn sphere_uv(p: &Vec3) -> (f64, f64) {
 
     (u, v)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle_positions() -> Vec<Vec3> {
+        vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ]
+    }
+
+    #[test]
+    fn mesh_accepts_faces_in_range() {
+        let faces = vec![Face::new([0, 1, 2], None, Some([0, 0, 0]), 0)];
+        let materials = vec![Material::lambertian(0.5, 0.5, 0.5)];
+        let mesh = Mesh::new(
+            triangle_positions(),
+            Vec::new(),
+            vec![(0.0, 0.0)],
+            faces,
+            materials,
+        );
+
+        assert_eq!(mesh.unwrap().faces().len(), 1);
+    }
+
+    #[test]
+    fn mesh_rejects_faces_out_of_range() {
+        let material = || vec![Material::lambertian(0.5, 0.5, 0.5)];
+        let faces = [
+            Face::new([0, 1, 3], None, None, 0),
+            Face::new([0, 1, 2], Some([0, 0, 0]), None, 0),
+            Face::new([0, 1, 2], None, Some([0, 1, 0]), 0),
+            Face::new([0, 1, 2], None, None, 1),
+        ];
+
+        for face in faces.iter() {
+            let mesh = Mesh::new(
+                triangle_positions(),
+                Vec::new(),
+                vec![(0.0, 0.0)],
+                vec![face.clone()],
+                material(),
+            );
+            assert!(matches!(mesh, Err(Error::Scene(_))), "{:?}", face);
+        }
+    }
+
+    #[test]
+    fn moving_bounds_hold_every_pose() {
+        let material = Material::lambertian(0.5, 0.5, 0.5);
+        let cube = Shape::cube(-1.0, -0.5, -0.25, 1.0, 0.5, 0.25, material);
+        let keyframes = vec![
+            Keyframe::new(0.0, Vec3::default(), Vec3::default()).curve(Curve::Smoothstep),
+            Keyframe::new(1.0, Vec3::new(2.0, 0.0, 1.0), Vec3::new(0.0, 90.0, 20.0))
+                .curve(Curve::Bezier([0.9, 0.0, 1.0, 0.1])),
+            Keyframe::new(1.5, Vec3::new(2.0, -1.0, 1.0), Vec3::new(45.0, 270.0, 20.0)),
+            Keyframe::new(2.0, Vec3::new(2.0, -1.0, 1.0), Vec3::new(45.0, 990.0, 20.0)),
+        ];
+        let moving = Moving::new(cube, keyframes, Vec3::new(0.5, 0.0, 0.0));
+
+        for &(time_start, time_end) in &[
+            (0.0, 2.0),
+            (0.0, 0.1),
+            (0.2, 1.3),
+            (1.55, 1.7),
+            (1.6, 1.65),
+            (1.7, 1.9),
+            (-1.0, 3.0),
+        ] {
+            let bounds: &dyn Hittable<'_, rand::rngs::SmallRng> = &moving;
+            let aabb = bounds.bounding_box(time_start, time_end).unwrap();
+
+            for sample in 0..=2000 {
+                let time = time_start + (time_end - time_start) * sample as f64 / 2000.0;
+                let transform = moving.transform(time);
+                for corner in 0..8 {
+                    let x = if corner & 1 == 0 { -1.0 } else { 1.0 };
+                    let y = if corner & 2 == 0 { -0.5 } else { 0.5 };
+                    let z = if corner & 4 == 0 { -0.25 } else { 0.25 };
+                    let p = transform.point_to_world(&Vec3::new(x, y, z));
+
+                    for axis in 0..3 {
+                        assert!(
+                            aabb.min()[axis] - 1e-9 <= p[axis]
+                                && p[axis] <= aabb.max()[axis] + 1e-9,
+                            "{:?} at {} is outside {:?} over {}-{}",
+                            p,
+                            time,
+                            aabb,
+                            time_start,
+                            time_end
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+#![deny(rust_2018_idioms)]
+#![allow(clippy::too_many_arguments, clippy::upper_case_acronyms)]
+
+pub use crate::{
+    camera::{Camera, CameraBuilder},
+    error::{Error, Result},
+    renderer::{Framebuffer, Renderer},
+    scene::{Scene, SceneBuilder},
+};
+
+pub mod aabb;
+pub mod animation;
+pub mod background;
+pub mod bvh;
+pub mod camera;
+pub mod config;
+pub mod density;
+pub mod error;
+mod exr;
+pub mod hittable;
+mod light;
+pub mod material;
+pub mod obj;
+mod onb;
+pub mod output;
+mod perlin;
+mod ppm;
+pub mod ray;
+pub mod renderer;
+pub mod scene;
+pub mod texture;
+pub mod tonemap;
+pub mod transform;
+mod util;
+pub mod vec3;
+pub mod world;
@@ -0,0 +1,222 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use crate::{
+    background::{Background, EnvironmentMap},
+    bvh::BoundingVolumeHierarchy,
+    onb::Onb,
+    ray::Ray,
+    util::RandomDouble,
+    vec3::Vec3,
+};
+
+/// Geometry of an emissive shape that can be sampled directly.
+///
+/// Only the shape is kept; the emitted radiance comes from whatever the shadow
+/// ray hits, so the light's material is never needed here.
+#[derive(Clone, Debug)]
+pub enum Light {
+    Sphere {
+        center: Vec3,
+        radius: f64,
+    },
+    XyRect {
+        x0: f64,
+        x1: f64,
+        y0: f64,
+        y1: f64,
+        k: f64,
+    },
+    XzRect {
+        x0: f64,
+        x1: f64,
+        z0: f64,
+        z1: f64,
+        k: f64,
+    },
+    YzRect {
+        y0: f64,
+        y1: f64,
+        z0: f64,
+        z1: f64,
+        k: f64,
+    },
+}
+
+impl Light {
+    /// Direction from `origin` towards a random point on the light, or `None` if
+    /// the light can't be sampled from there.
+    fn sample<T: Rng>(&self, rng: &mut T, origin: &Vec3) -> Option<Vec3> {
+        let point = match self {
+            Light::Sphere { center, radius } => {
+                return sample_sphere(rng, origin, center, *radius);
+            }
+            Light::XyRect { x0, x1, y0, y1, k } => Vec3::new(
+                x0 + rng.random_double() * (x1 - x0),
+                y0 + rng.random_double() * (y1 - y0),
+                *k,
+            ),
+            Light::XzRect { x0, x1, z0, z1, k } => Vec3::new(
+                x0 + rng.random_double() * (x1 - x0),
+                *k,
+                z0 + rng.random_double() * (z1 - z0),
+            ),
+            Light::YzRect { y0, y1, z0, z1, k } => Vec3::new(
+                *k,
+                y0 + rng.random_double() * (y1 - y0),
+                z0 + rng.random_double() * (z1 - z0),
+            ),
+        };
+
+        Some(point - origin)
+    }
+
+    /// Solid angle density of `sample` generating the direction of `ray`, which
+    /// doesn't depend on whether something blocks the light.
+    fn pdf(&self, ray: &Ray) -> f64 {
+        match self {
+            Light::Sphere { center, radius } => {
+                let oc = ray.origin() - center;
+
+                let a = ray.direction().dot(ray.direction());
+                let b = oc.dot(ray.direction());
+                let c = oc.dot(&oc) - radius * radius;
+
+                let discriminant = b * b - a * c;
+                if c <= 0.0 || discriminant < 0.0 {
+                    return 0.0;
+                }
+
+                // Outside the sphere the ray either reaches it in front or not at all
+                if b > 0.0 {
+                    return 0.0;
+                }
+
+                let cos_theta_max = (1.0 - radius * radius / oc.square_length()).sqrt();
+                1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+            }
+            Light::XyRect { x0, x1, y0, y1, k } => {
+                rect_pdf(ray, 2, *k, (0, *x0, *x1), (1, *y0, *y1))
+            }
+            Light::XzRect { x0, x1, z0, z1, k } => {
+                rect_pdf(ray, 1, *k, (0, *x0, *x1), (2, *z0, *z1))
+            }
+            Light::YzRect { y0, y1, z0, z1, k } => {
+                rect_pdf(ray, 0, *k, (1, *y0, *y1), (2, *z0, *z1))
+            }
+        }
+    }
+}
+
+/// Every light in a world, including an environment map background, each
+/// picked with equal probability when sampling.
+#[derive(Debug, Default)]
+pub struct Lights<'a> {
+    lights: Vec<Light>,
+    environment: Option<&'a EnvironmentMap>,
+}
+
+impl<'a> Lights<'a> {
+    pub fn new(world: &BoundingVolumeHierarchy, background: &'a Background) -> Self {
+        let lights = world
+            .shapes()
+            .iter()
+            .filter_map(|shape| shape.light())
+            .collect();
+        let environment = background.environment_map();
+
+        Self {
+            lights,
+            environment,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn len(&self) -> usize {
+        self.lights.len() + self.environment.iter().count()
+    }
+
+    pub fn sample<T: Rng>(&self, rng: &mut T, origin: &Vec3) -> Option<Vec3> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let index = rng.gen_range(0, self.len());
+        match self.lights.get(index) {
+            Some(light) => light.sample(rng, origin),
+            None => self.environment.map(|environment| environment.sample(rng)),
+        }
+    }
+
+    /// Density of `sample` producing the direction of `ray`.
+    pub fn pdf(&self, ray: &Ray) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let lights: f64 = self.lights.iter().map(|light| light.pdf(ray)).sum();
+        let environment = self
+            .environment
+            .map_or(0.0, |environment| environment.pdf(ray.direction()));
+
+        (lights + environment) / self.len() as f64
+    }
+}
+
+/// Samples the cone of directions the sphere covers as seen from `origin`.
+fn sample_sphere<T: Rng>(rng: &mut T, origin: &Vec3, center: &Vec3, radius: f64) -> Option<Vec3> {
+    let direction = center - origin;
+    let distance_squared = direction.square_length();
+    if distance_squared <= radius * radius {
+        return None;
+    }
+
+    let cos_theta_max = (1.0 - radius * radius / distance_squared).sqrt();
+
+    let r1 = rng.random_double();
+    let r2 = rng.random_double();
+    let z = 1.0 + r2 * (cos_theta_max - 1.0);
+    let phi = 2.0 * PI * r1;
+    let sin_theta = (1.0 - z * z).sqrt();
+
+    let uvw = Onb::from_w(&direction);
+    let local = Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z);
+
+    Some(uvw.local(&local))
+}
+
+/// Density of uniformly sampling an axis-aligned rect lying at `k` along `axis`,
+/// converted from area to solid angle.
+fn rect_pdf(
+    ray: &Ray,
+    axis: usize,
+    k: f64,
+    (a_axis, a0, a1): (usize, f64, f64),
+    (b_axis, b0, b1): (usize, f64, f64),
+) -> f64 {
+    let direction = ray.direction();
+    if direction[axis] == 0.0 {
+        return 0.0;
+    }
+
+    let t_rect = (k - ray.origin()[axis]) / direction[axis];
+    if t_rect <= 0.0 {
+        return 0.0;
+    }
+
+    let p = ray.point_at(t_rect);
+    if p[a_axis] < a0 || p[a_axis] > a1 || p[b_axis] < b0 || p[b_axis] > b1 {
+        return 0.0;
+    }
+
+    let area = (a1 - a0) * (b1 - b0);
+    let length_squared = direction.square_length();
+    let distance_squared = t_rect * t_rect * length_squared;
+    let cosine = direction[axis].abs() / length_squared.sqrt();
+
+    distance_squared / (cosine * area)
+}
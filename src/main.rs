@@ -1,102 +1,241 @@
 #![deny(rust_2018_idioms)]
 
-use std::{env, f64, time::Instant};
+mod cli;
 
-use rand::{rngs::SmallRng, SeedableRng};
+use std::{env, process, time::Instant};
 
-use crate::{
-    camera::Camera, config::read_tracer_config, renderer::render_world, vec3::Vec3,
-    world::gen_world,
+use ray_tracer::{
+    animation::{frame_path, CameraPose, CameraTrack},
+    config::{read_tracer_config_with_overrides, TracerConfig},
+    vec3::Vec3,
+    Camera, Error, Renderer, Result, Scene,
 };
 
-mod aabb;
-mod bvh;
-mod camera;
-mod config;
-mod hittable;
-mod material;
-mod perlin;
-mod ppm;
-mod ray;
-mod renderer;
-mod texture;
-mod util;
-mod vec3;
-mod world;
+use crate::cli::{Args, Command, USAGE};
 
 fn main() {
-    let tracer_config = {
-        let config_path = env::var("TRACER_CONFIG_PATH")
-            .unwrap_or_else(|_| "./resources/tracer.toml".to_string());
-        read_tracer_config(config_path)
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
     };
 
-    let screen_width = tracer_config.renderer_config().screen_width();
-    let screen_height = tracer_config.renderer_config().screen_height();
-
-    let camera = {
-        let camera_config = tracer_config.camera_config();
-        let look_from = camera_config.look_from().clone();
-        let look_at = camera_config.look_at().clone();
-        let time_start = camera_config.time_start();
-        let time_end = camera_config.time_end();
-
-        let v_up = Vec3::new(0.0, 1.0, 0.0);
-
-        let focus_distance = 10.0; //(&look_from - &look_at).length();
-
-        Camera::new(
-            look_from,
-            look_at,
-            v_up,
-            camera_config.fov(),
-            screen_width as f64 / screen_height as f64,
-            camera_config.aperture(),
-            focus_distance,
-            time_start,
-            time_end,
-        )
-    };
+    if args.help() {
+        print!("{}", USAGE);
+        return;
+    }
+
+    if let Err(e) = run(&args) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
 
+fn run(args: &Args) -> Result<()> {
+    let tracer_config = read_tracer_config_with_overrides(args.config_path(), args.overrides())?;
+
+    if args.dump_config() {
+        print!("{}", tracer_config.to_toml_string());
+        return Ok(());
+    }
+
+    // TOML integers are signed, so keep it small enough to paste into a config
+    let seed = tracer_config
+        .renderer_config()
+        .seed()
+        .unwrap_or_else(|| rand::random::<u64>() >> 1);
+    println!("Using seed {}", seed);
+
+    match args.command() {
+        Command::Render => render(&tracer_config, seed),
+        Command::Inspect => inspect(&tracer_config, seed),
+    }
+}
+
+fn render(tracer_config: &TracerConfig, seed: u64) -> Result<()> {
     let world_gen_start = Instant::now();
     println!("Start world gen");
-    let world = {
-        let mut rng = SmallRng::from_entropy();
+    let mut scene = Scene::from_config(tracer_config, seed)?;
+    println!("End world gen-- took {:.2?}", world_gen_start.elapsed());
 
-        let time_start = tracer_config.camera_config().time_start();
-        let time_end = tracer_config.camera_config().time_end();
+    let camera_config = tracer_config.camera_config();
+    let output_path = tracer_config.output_config().output_path();
 
-        gen_world(&mut rng, tracer_config.world_config(), time_start, time_end)
+    let animation_config = match tracer_config.animation_config() {
+        Some(animation_config) => animation_config,
+        None => {
+            let pose = CameraPose {
+                look_from: camera_config.look_from().clone(),
+                look_at: camera_config.look_at().clone(),
+                fov: camera_config.fov(),
+            };
+            let time = (camera_config.time_start(), camera_config.time_end());
+            let camera = build_camera(tracer_config, &scene, &pose, time)?;
+
+            return render_frame(tracer_config, &scene, &camera, seed, output_path);
+        }
     };
 
-    println!("End world gen-- took {:.2?}", world_gen_start.elapsed());
+    let fps = animation_config.fps();
+    if !(fps > 0.0 && fps.is_finite()) {
+        return Err(Error::scene(format!(
+            "Animation fps {} must be a positive number",
+            fps
+        )));
+    }
+
+    let shutter = animation_config.shutter();
+    if !(shutter >= 0.0 && shutter.is_finite()) {
+        return Err(Error::scene(format!(
+            "Animation shutter {} must be a number no less than 0",
+            shutter
+        )));
+    }
+
+    let track = CameraTrack::from_config(camera_config, animation_config)?;
+    let frames = animation_config.frames();
+    for frame in 0..frames {
+        println!("Frame {} of {}", frame + 1, frames);
+
+        let time_start = frame as f64 / fps;
+        let time_end = time_start + shutter / fps;
+        scene.set_time(time_start, time_end);
+
+        let pose = track.pose(time_start);
+        let camera = build_camera(tracer_config, &scene, &pose, (time_start, time_end))?;
+
+        // Fresh noise every frame, or it looks like a dirty lens
+        let frame_seed = seed.wrapping_add(frame as u64);
+        let path = frame_path(output_path, frame);
+        render_frame(tracer_config, &scene, &camera, frame_seed, &path)?;
+    }
+
+    Ok(())
+}
+
+fn build_camera(
+    tracer_config: &TracerConfig,
+    scene: &Scene,
+    pose: &CameraPose,
+    (time_start, time_end): (f64, f64),
+) -> Result<Camera> {
+    let renderer_config = tracer_config.renderer_config();
+    let aspect = renderer_config.screen_width() as f64 / renderer_config.screen_height() as f64;
+    let camera_config = tracer_config.camera_config();
+
+    // Without a view direction and an up that isn't along it, there's no basis to
+    // build the image plane from
+    let view = &pose.look_at - &pose.look_from;
+    if !(view.length() > 0.0 && view.is_finite()) {
+        return Err(Error::scene(format!(
+            "Camera look_from {:?} and look_at {:?} must be different points",
+            pose.look_from, pose.look_at
+        )));
+    }
+
+    let v_up = camera_config.v_up();
+    if !v_up.is_finite() || v_up.cross(&view).length() <= 1e-9 * v_up.length() * view.length() {
+        return Err(Error::scene(format!(
+            "Camera v_up {:?} must not be zero or along the view direction {:?}",
+            v_up, view
+        )));
+    }
+
+    let builder = Camera::builder()
+        .projection(camera_config.projection())
+        .look_from(pose.look_from.clone())
+        .look_at(pose.look_at.clone())
+        .v_up(camera_config.v_up().clone())
+        .vertical_fov(pose.fov)
+        .aspect(aspect)
+        .aperture(camera_config.aperture())
+        .focus_distance(camera_config.focus_distance())
+        .time(time_start, time_end);
+
+    let camera = if camera_config.autofocus() {
+        let (s, t) = camera_config.focus_point();
+        builder.autofocus(scene, s, t).build()
+    } else {
+        builder.build()
+    };
+
+    Ok(camera)
+}
+
+fn render_frame(
+    tracer_config: &TracerConfig,
+    scene: &Scene,
+    camera: &Camera,
+    seed: u64,
+    output_path: &str,
+) -> Result<()> {
+    let renderer_config = tracer_config.renderer_config();
 
     let tracing_start = Instant::now();
     println!("Start tracing");
 
-    let antialias_iterations = tracer_config.renderer_config().antialias_iterations();
-    let render_parallel = tracer_config.renderer_config().render_parallel();
-    let use_bounding_volume = tracer_config.renderer_config().use_bounding_volume();
-    // let background = Vec3::new(0.70, 0.80, 1.00);
-    let background = Vec3::new(0.0, 0.0, 0.0);
-
-    let buffer: Vec<u8> = render_world(
-        &background,
-        &world,
-        &camera,
-        screen_width,
-        screen_height,
-        antialias_iterations,
-        render_parallel,
-        use_bounding_volume,
-    );
+    let framebuffer = Renderer::new(
+        renderer_config.screen_width(),
+        renderer_config.screen_height(),
+    )
+    .samples(renderer_config.antialias_iterations())
+    .parallel(renderer_config.render_parallel())
+    .use_bounding_volume(renderer_config.use_bounding_volume())
+    .seed(seed)
+    .threads(renderer_config.threads())
+    .render(scene, camera);
 
     println!("End tracing-- took {:.2?}", tracing_start.elapsed());
 
-    let ppm_start = Instant::now();
-    println!("Start ppm creation");
+    let output_start = Instant::now();
+    println!("Start image creation");
 
-    let output_path = tracer_config.output_config().output_path();
-    ppm::create(output_path, screen_width, screen_height, &buffer);
-    println!("End ppm creation-- took {:.2?}", ppm_start.elapsed());
+    let output_config = tracer_config.output_config();
+    framebuffer.save(
+        output_path,
+        output_config.file_type(),
+        output_config.tone_map(),
+        output_config.exposure(),
+    )?;
+    println!("End image creation-- took {:.2?}", output_start.elapsed());
+
+    Ok(())
+}
+
+fn inspect(tracer_config: &TracerConfig, seed: u64) -> Result<()> {
+    let scene = Scene::from_config(tracer_config, seed)?;
+    let world = scene.world();
+    let lights = world
+        .shapes()
+        .iter()
+        .filter(|shape| shape.light().is_some())
+        .count();
+
+    println!("Scene:      {:?}", tracer_config.world_config());
+    println!("Shapes:     {}", world.shapes().len());
+    println!("Lights:     {}", lights);
+    println!(
+        "BVH:        {} nodes, {} deep, {:?} split, SAH cost {:.2}",
+        world.nodes().len(),
+        world.depth(),
+        tracer_config.renderer_config().bvh_split(),
+        world.cost()
+    );
+    match world.bounds() {
+        Some(bounds) => println!(
+            "Bounds:     {} to {}",
+            format_vec(bounds.min()),
+            format_vec(bounds.max())
+        ),
+        None => println!("Bounds:     empty"),
+    }
+    println!("Background: {:?}", tracer_config.background_config());
+
+    Ok(())
+}
+
+fn format_vec(vec: &Vec3) -> String {
+    format!("({:.2}, {:.2}, {:.2})", vec.x(), vec.y(), vec.z())
 }
@@ -1,7 +1,10 @@
+use std::f64::consts::PI;
+
 use rand::Rng;
 
 use crate::{
     hittable::Hit,
+    onb::Onb,
     ray::Ray,
     texture::{Texturable, Texture},
     util::RandomDouble,
@@ -13,18 +16,39 @@ pub trait Scatterable<T: Rng> {
     fn emit(&self, _u: f64, _v: f64, _p: &Vec3) -> Vec3 {
         Vec3::default()
     }
+    /// Density of `scatter` producing `scattered`, which is zero for materials that
+    /// only scatter in a single direction.
+    fn scattering_pdf(&self, _ray: &Ray, _hit: &Hit<'_, T>, _scattered: &Ray) -> f64 {
+        0.0
+    }
 }
 
 pub struct ScatterResponse {
     scattered: Ray,
     attenuation: Vec3,
+    pdf: f64,
+    is_specular: bool,
 }
 
 impl ScatterResponse {
-    pub fn new(scattered: Ray, attenuation: Vec3) -> ScatterResponse {
+    /// `pdf` is the density `scattered` was sampled with, which the renderer weighs
+    /// against `Scatterable::scattering_pdf`.
+    pub fn new(scattered: Ray, attenuation: Vec3, pdf: f64) -> ScatterResponse {
+        ScatterResponse {
+            scattered,
+            attenuation,
+            pdf,
+            is_specular: false,
+        }
+    }
+
+    /// A scatter into a single direction, which can't be importance sampled.
+    pub fn specular(scattered: Ray, attenuation: Vec3) -> ScatterResponse {
         ScatterResponse {
             scattered,
             attenuation,
+            pdf: 0.0,
+            is_specular: true,
         }
     }
 
@@ -35,6 +59,14 @@ impl ScatterResponse {
     pub fn attenuation(&self) -> &Vec3 {
         &self.attenuation
     }
+
+    pub fn pdf(&self) -> f64 {
+        self.pdf
+    }
+
+    pub fn is_specular(&self) -> bool {
+        self.is_specular
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -42,20 +74,28 @@ pub struct Lambertian {
     albedo: Texture,
 }
 
-impl<'a> Lambertian {
+impl Lambertian {
     pub fn new(albedo: Texture) -> Lambertian {
         Lambertian { albedo }
     }
 }
 
-impl<'a, T: Rng> Scatterable<T> for Lambertian {
+impl<T: Rng> Scatterable<T> for Lambertian {
     fn scatter(&self, rng: &mut T, ray: &Ray, hit: &Hit<'_, T>) -> Option<ScatterResponse> {
-        let target = hit.p() + hit.normal() + random_in_unit_sphere(rng);
-        let scattered = Ray::new(hit.p().clone(), target - hit.p(), ray.time());
+        let uvw = Onb::from_w(hit.normal());
+        let direction = uvw.local(&random_cosine_direction(rng));
+        let pdf = uvw.w().dot(&direction) / PI;
+
+        let scattered = Ray::new(hit.p().clone(), direction, ray.time());
         let attenuation = self.albedo.value(hit.u(), hit.v(), hit.p());
-        let response = ScatterResponse::new(scattered, attenuation);
+        let response = ScatterResponse::new(scattered, attenuation, pdf);
         Some(response)
     }
+
+    fn scattering_pdf(&self, _ray: &Ray, hit: &Hit<'_, T>, scattered: &Ray) -> f64 {
+        let cosine = hit.normal().dot(&scattered.direction().unit());
+        cosine.max(0.0) / PI
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -82,7 +122,7 @@ impl<T: Rng> Scatterable<T> for Metal {
 
         if scattered.direction().dot(hit.normal()) > 0.0 {
             let attenuation = self.albedo.clone();
-            let response = ScatterResponse::new(scattered, attenuation);
+            let response = ScatterResponse::specular(scattered, attenuation);
             Some(response)
         } else {
             None
@@ -124,7 +164,7 @@ impl<T: Rng> Scatterable<T> for Dielectric {
 
         let attenuation = Vec3::new(1.0, 1.0, 1.0);
         let refracted = Ray::new(hit.p().clone(), direction, ray.time());
-        let scatter = ScatterResponse::new(refracted, attenuation);
+        let scatter = ScatterResponse::specular(refracted, attenuation);
 
         Some(scatter)
     }
@@ -151,12 +191,93 @@ impl<T: Rng> Scatterable<T> for DiffuseLight {
     }
 }
 
+/// Scatters equally in every direction, for the inside of a volume.
+#[derive(Clone, Debug)]
+pub struct Isotropic {
+    albedo: Texture,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Texture) -> Self {
+        Self { albedo }
+    }
+}
+
+impl<T: Rng> Scatterable<T> for Isotropic {
+    fn scatter(&self, rng: &mut T, ray: &Ray, hit: &Hit<'_, T>) -> Option<ScatterResponse> {
+        let direction = random_in_unit_sphere(rng).into_unit();
+        let scattered = Ray::new(hit.p().clone(), direction, ray.time());
+        let attenuation = self.albedo.value(hit.u(), hit.v(), hit.p());
+
+        let response = ScatterResponse::new(scattered, attenuation, 1.0 / (4.0 * PI));
+        Some(response)
+    }
+
+    fn scattering_pdf(&self, _ray: &Ray, _hit: &Hit<'_, T>, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+/// A phase function leaning forward or backward by `anisotropy`, from -1 (all
+/// light bounces back) through 0 (isotropic) to 1 (all light carries on), though
+/// it's held within ±0.99. Clouds and haze scatter mostly forward, at around 0.8.
+#[derive(Clone, Debug)]
+pub struct HenyeyGreenstein {
+    albedo: Texture,
+    anisotropy: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Texture, anisotropy: f64) -> Self {
+        // At ±1 the lobe collapses into a single direction
+        let anisotropy = anisotropy.clamp(-0.99, 0.99);
+        Self { albedo, anisotropy }
+    }
+
+    /// Density of scattering at an angle with cosine `cos_theta` to the way the
+    /// light was travelling.
+    fn phase(&self, cos_theta: f64) -> f64 {
+        let g = self.anisotropy;
+        let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+    }
+}
+
+impl<T: Rng> Scatterable<T> for HenyeyGreenstein {
+    fn scatter(&self, rng: &mut T, ray: &Ray, hit: &Hit<'_, T>) -> Option<ScatterResponse> {
+        let g = self.anisotropy;
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * rng.random_double()
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * rng.random_double());
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.random_double();
+
+        let uvw = Onb::from_w(ray.direction());
+        let local = Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta);
+        let scattered = Ray::new(hit.p().clone(), uvw.local(&local), ray.time());
+        let attenuation = self.albedo.value(hit.u(), hit.v(), hit.p());
+
+        let response = ScatterResponse::new(scattered, attenuation, self.phase(cos_theta));
+        Some(response)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, _hit: &Hit<'_, T>, scattered: &Ray) -> f64 {
+        let cos_theta = ray.direction().unit().dot(&scattered.direction().unit());
+        self.phase(cos_theta)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Material {
     Lambertian(Lambertian),
     Dielectric(Dielectric),
     Metal(Metal),
     DiffuseLight(DiffuseLight),
+    Isotropic(Isotropic),
+    HenyeyGreenstein(HenyeyGreenstein),
 }
 
 impl Material {
@@ -186,15 +307,31 @@ impl Material {
         let material = DiffuseLight::new(texture);
         Self::DiffuseLight(material)
     }
+
+    pub fn isotropic(texture: Texture) -> Self {
+        let material = Isotropic::new(texture);
+        Self::Isotropic(material)
+    }
+
+    pub fn henyey_greenstein(texture: Texture, anisotropy: f64) -> Self {
+        let material = HenyeyGreenstein::new(texture, anisotropy);
+        Self::HenyeyGreenstein(material)
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        matches!(self, Material::DiffuseLight(_))
+    }
 }
 
-impl<'a, T: Rng> Scatterable<T> for Material {
+impl<T: Rng> Scatterable<T> for Material {
     fn scatter(&self, rng: &mut T, ray: &Ray, hit: &Hit<'_, T>) -> Option<ScatterResponse> {
         match self {
             Material::Lambertian(material) => material.scatter(rng, ray, hit),
             Material::Dielectric(material) => material.scatter(rng, ray, hit),
             Material::Metal(material) => material.scatter(rng, ray, hit),
             Material::DiffuseLight(material) => material.scatter(rng, ray, hit),
+            Material::Isotropic(material) => material.scatter(rng, ray, hit),
+            Material::HenyeyGreenstein(material) => material.scatter(rng, ray, hit),
         }
     }
 
@@ -204,6 +341,19 @@ impl<'a, T: Rng> Scatterable<T> for Material {
             Material::Dielectric(material) => <dyn Scatterable<T>>::emit(material, u, v, p),
             Material::Metal(material) => <dyn Scatterable<T>>::emit(material, u, v, p),
             Material::DiffuseLight(material) => <dyn Scatterable<T>>::emit(material, u, v, p),
+            Material::Isotropic(material) => <dyn Scatterable<T>>::emit(material, u, v, p),
+            Material::HenyeyGreenstein(material) => <dyn Scatterable<T>>::emit(material, u, v, p),
+        }
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit: &Hit<'_, T>, scattered: &Ray) -> f64 {
+        match self {
+            Material::Lambertian(material) => material.scattering_pdf(ray, hit, scattered),
+            Material::Dielectric(material) => material.scattering_pdf(ray, hit, scattered),
+            Material::Metal(material) => material.scattering_pdf(ray, hit, scattered),
+            Material::DiffuseLight(material) => material.scattering_pdf(ray, hit, scattered),
+            Material::Isotropic(material) => material.scattering_pdf(ray, hit, scattered),
+            Material::HenyeyGreenstein(material) => material.scattering_pdf(ray, hit, scattered),
         }
     }
 }
@@ -224,6 +374,19 @@ fn random_in_unit_sphere<T: Rng>(rng: &mut T) -> Vec3 {
     p
 }
 
+/// Direction around the Z axis with a density proportional to its cosine.
+fn random_cosine_direction<T: Rng>(rng: &mut T) -> Vec3 {
+    let r1 = rng.random_double();
+    let r2 = rng.random_double();
+
+    let phi = 2.0 * PI * r1;
+    let x = phi.cos() * r2.sqrt();
+    let y = phi.sin() * r2.sqrt();
+    let z = (1.0 - r2).sqrt();
+
+    Vec3::new(x, y, z)
+}
+
 fn reflect(v: &Vec3, n: &Vec3) -> Vec3 {
     v - &(2.0 * v.dot(n) * n)
 }
@@ -241,3 +404,84 @@ fn schlick(cosine: f64, ref_idx: f64) -> f64 {
     r0 *= r0;
     r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+
+    /// Compares a histogram of the angles `material` scatters at with the share
+    /// of its phase function over each bin.
+    fn check_sampling(anisotropy: f64) {
+        const BINS: usize = 16;
+        const SAMPLES: usize = 200_000;
+
+        let material = HenyeyGreenstein::new(Texture::constant(1.0, 1.0, 1.0), anisotropy);
+        let ray = Ray::new(Vec3::default(), Vec3::new(0.0, 0.0, 2.0), 0.0);
+        let normal = Vec3::new(1.0, 0.0, 0.0);
+        let hit = Hit::new(0.0, 0.0, 0.0, Vec3::default(), &ray, normal, &material);
+
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut histogram = [0.0; BINS];
+        for _ in 0..SAMPLES {
+            let scatter = material.scatter(&mut rng, &ray, &hit).unwrap();
+            let scattered = scatter.scattered();
+            let pdf = material.scattering_pdf(&ray, &hit, scattered);
+            assert!((scatter.pdf() - pdf).abs() <= 1e-9 * pdf);
+
+            let cos_theta = scattered.direction().unit().z();
+            let bin = ((cos_theta + 1.0) / 2.0 * BINS as f64) as usize;
+            histogram[bin.min(BINS - 1)] += 1.0 / SAMPLES as f64;
+        }
+
+        for (bin, &sampled) in histogram.iter().enumerate() {
+            // Midpoint rule over the bin, around the whole circle of phi
+            let width = 2.0 / BINS as f64;
+            let steps = 100;
+            let expected: f64 = (0..steps)
+                .map(|step| {
+                    let cos_theta =
+                        -1.0 + width * (bin as f64 + (step as f64 + 0.5) / steps as f64);
+                    2.0 * PI * material.phase(cos_theta) * width / steps as f64
+                })
+                .sum();
+
+            assert!(
+                (sampled - expected).abs() < 0.005,
+                "bin {} sampled {} but expected {}",
+                bin,
+                sampled,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn henyey_greenstein_samples_its_phase_function() {
+        for &anisotropy in &[-0.5, 0.0, 0.3, 0.8] {
+            check_sampling(anisotropy);
+        }
+    }
+
+    #[test]
+    fn henyey_greenstein_phase_integrates_to_one() {
+        for &anisotropy in &[-0.9, 0.0, 0.5, 0.9] {
+            let material = HenyeyGreenstein::new(Texture::constant(1.0, 1.0, 1.0), anisotropy);
+            let steps = 100_000;
+            let total: f64 = (0..steps)
+                .map(|step| {
+                    let cos_theta = -1.0 + 2.0 * (step as f64 + 0.5) / steps as f64;
+                    2.0 * PI * material.phase(cos_theta) * 2.0 / steps as f64
+                })
+                .sum();
+
+            assert!(
+                (total - 1.0).abs() < 1e-3,
+                "{} integrates to {}",
+                anisotropy,
+                total
+            );
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::{
+    error::{self, Error},
+    hittable::{Face, Mesh},
+    material::Material,
+    texture::Texture,
+    vec3::Vec3,
+};
+
+/// Loads a Wavefront OBJ file, along with any MTL libraries it references, as a
+/// single triangle mesh. Faces before any `usemtl` use `default_material`, and
+/// polygons are split into a fan of triangles around their first vertex.
+pub fn load<P: AsRef<Path>>(path: P, default_material: Material) -> error::Result<Mesh> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+    parse(&source, path, default_material)
+}
+
+/// Parses the contents of the OBJ file at `path`, which MTL libraries are looked
+/// up next to and errors are reported against.
+fn parse(source: &str, path: &Path, default_material: Material) -> error::Result<Mesh> {
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut faces = Vec::new();
+
+    let mut library = HashMap::new();
+    let mut materials = vec![default_material];
+    let mut material_indices: HashMap<String, usize> = HashMap::new();
+    let mut current_material = 0;
+
+    for (line_number, line) in source.lines().enumerate() {
+        let error = |message: String| Error::Parse {
+            path: path.to_path_buf(),
+            line: line_number + 1,
+            message,
+        };
+
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => positions.push(parse_vec3(&arguments).map_err(error)?),
+            "vn" => normals.push(parse_vec3(&arguments).map_err(error)?),
+            "vt" => {
+                let uv = parse_floats(&arguments).map_err(error)?;
+                let u = uv.first().copied().unwrap_or(0.0);
+                let v = uv.get(1).copied().unwrap_or(0.0);
+                uvs.push((u, v));
+            }
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(error("faces need at least three vertices".to_string()));
+                }
+
+                let vertices = arguments
+                    .iter()
+                    .map(|vertex| {
+                        parse_face_vertex(vertex, positions.len(), uvs.len(), normals.len())
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+
+                for i in 1..vertices.len() - 1 {
+                    let triangle = [&vertices[0], &vertices[i], &vertices[i + 1]];
+                    let positions = [triangle[0].0, triangle[1].0, triangle[2].0];
+
+                    let uvs = match (triangle[0].1, triangle[1].1, triangle[2].1) {
+                        (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+                        _ => None,
+                    };
+                    let normals = match (triangle[0].2, triangle[1].2, triangle[2].2) {
+                        (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+                        _ => None,
+                    };
+
+                    faces.push(Face::new(positions, normals, uvs, current_material));
+                }
+            }
+            "mtllib" => {
+                for file_name in arguments {
+                    let mtl_path = directory.join(file_name);
+                    library.extend(load_mtl(&mtl_path)?);
+                }
+            }
+            "usemtl" => {
+                let name = arguments.join(" ");
+                current_material = match material_indices.get(&name) {
+                    Some(&index) => index,
+                    None => match library.get(&name) {
+                        Some(material) => {
+                            materials.push(material.clone());
+                            material_indices.insert(name, materials.len() - 1);
+                            materials.len() - 1
+                        }
+                        None => return Err(error(format!("unknown material {:?}", name))),
+                    },
+                };
+            }
+            // Groups, objects and smoothing groups don't affect how the mesh is rendered
+            _ => {}
+        }
+    }
+
+    Mesh::new(positions, normals, uvs, faces, materials)
+}
+
+fn load_mtl(path: &Path) -> error::Result<HashMap<String, Material>> {
+    let source = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut definitions: Vec<(String, MtlDefinition)> = Vec::new();
+
+    for (line_number, line) in source.lines().enumerate() {
+        let error = |message: String| Error::Parse {
+            path: path.to_path_buf(),
+            line: line_number + 1,
+            message,
+        };
+
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            definitions.push((arguments.join(" "), MtlDefinition::default()));
+            continue;
+        }
+
+        let definition = match definitions.last_mut() {
+            Some((_, definition)) => definition,
+            None => return Err(error(format!("`{}` before any `newmtl`", keyword))),
+        };
+
+        match keyword {
+            "Kd" => definition.diffuse = parse_vec3(&arguments).map_err(error)?,
+            "Ks" => definition.specular = parse_vec3(&arguments).map_err(error)?,
+            "Ke" => definition.emissive = parse_vec3(&arguments).map_err(error)?,
+            "Ns" => definition.shininess = parse_float(&arguments).map_err(error)?,
+            "Ni" => definition.optical_density = parse_float(&arguments).map_err(error)?,
+            "d" => definition.dissolve = parse_float(&arguments).map_err(error)?,
+            "Tr" => definition.dissolve = 1.0 - parse_float(&arguments).map_err(error)?,
+            "illum" => definition.illumination = parse_float(&arguments).map_err(error)? as u8,
+            // Options such as `-bm` come before the file name, so it's always last
+            "map_Kd" => {
+                let file_name = arguments
+                    .last()
+                    .ok_or_else(|| error("`map_Kd` needs a file name".to_string()))?;
+                definition.diffuse_map = Some(directory.join(file_name).display().to_string());
+            }
+            _ => {}
+        }
+    }
+
+    definitions
+        .into_iter()
+        .map(|(name, definition)| Ok((name, definition.into_material()?)))
+        .collect()
+}
+
+struct MtlDefinition {
+    diffuse: Vec3,
+    specular: Vec3,
+    emissive: Vec3,
+    shininess: f64,
+    optical_density: f64,
+    dissolve: f64,
+    illumination: u8,
+    diffuse_map: Option<String>,
+}
+
+impl Default for MtlDefinition {
+    fn default() -> Self {
+        Self {
+            diffuse: Vec3::new(0.8, 0.8, 0.8),
+            specular: Vec3::default(),
+            emissive: Vec3::default(),
+            shininess: 0.0,
+            optical_density: 1.5,
+            dissolve: 1.0,
+            illumination: 2,
+            diffuse_map: None,
+        }
+    }
+}
+
+impl MtlDefinition {
+    fn into_material(self) -> error::Result<Material> {
+        let is_black = |color: &Vec3| color.x() <= 0.0 && color.y() <= 0.0 && color.z() <= 0.0;
+
+        if !is_black(&self.emissive) {
+            let emissive = self.emissive;
+            return Ok(Material::diffuse_light(Texture::constant(
+                emissive.r(),
+                emissive.g(),
+                emissive.b(),
+            )));
+        }
+
+        // Illumination models 4, 6 and 7 are the refractive ones
+        let is_transparent = self.dissolve < 1.0 || matches!(self.illumination, 4 | 6 | 7);
+        if is_transparent {
+            return Ok(Material::dielectric(self.optical_density));
+        }
+
+        let is_reflective =
+            self.illumination == 3 || (is_black(&self.diffuse) && !is_black(&self.specular));
+        if is_reflective {
+            // Rough approximation of a Phong exponent as fuzziness
+            let fuzziness = (2.0 / (self.shininess + 2.0)).sqrt();
+            let specular = self.specular;
+            return Ok(Material::metal(
+                specular.r(),
+                specular.g(),
+                specular.b(),
+                fuzziness,
+            ));
+        }
+
+        let material = match self.diffuse_map {
+            Some(diffuse_map) => Material::textured(Texture::image(diffuse_map)?),
+            None => {
+                let diffuse = self.diffuse;
+                Material::lambertian(diffuse.r(), diffuse.g(), diffuse.b())
+            }
+        };
+
+        Ok(material)
+    }
+}
+
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+/// Parses a `v`, `v/vt`, `v//vn` or `v/vt/vn` face vertex into zero-based
+/// indices, resolving negative indices relative to the current counts.
+fn parse_face_vertex(
+    vertex: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+) -> Result<FaceVertex, String> {
+    let mut parts = vertex.split('/');
+
+    let resolve = |part: Option<&str>, count: usize| -> Result<Option<usize>, String> {
+        let part = match part {
+            Some(part) if !part.is_empty() => part,
+            _ => return Ok(None),
+        };
+
+        let index: i64 = part
+            .parse()
+            .map_err(|_| format!("invalid index {:?}", part))?;
+
+        let resolved = if index < 0 {
+            count as i64 + index
+        } else {
+            index - 1
+        };
+
+        if resolved < 0 || resolved >= count as i64 {
+            return Err(format!("index {} is out of range", index));
+        }
+
+        Ok(Some(resolved as usize))
+    };
+
+    let position = resolve(parts.next(), position_count)?
+        .ok_or_else(|| format!("face vertex {:?} has no position", vertex))?;
+    let uv = resolve(parts.next(), uv_count)?;
+    let normal = resolve(parts.next(), normal_count)?;
+
+    Ok((position, uv, normal))
+}
+
+fn parse_floats(arguments: &[&str]) -> Result<Vec<f64>, String> {
+    arguments
+        .iter()
+        .map(|argument| {
+            argument
+                .parse()
+                .map_err(|_| format!("invalid number {:?}", argument))
+        })
+        .collect()
+}
+
+fn parse_float(arguments: &[&str]) -> Result<f64, String> {
+    match parse_floats(arguments)?.first() {
+        Some(&value) => Ok(value),
+        None => Err("expected a number".to_string()),
+    }
+}
+
+fn parse_vec3(arguments: &[&str]) -> Result<Vec3, String> {
+    match parse_floats(arguments)?.as_slice() {
+        [x, y, z, ..] => Ok(Vec3::new(*x, *y, *z)),
+        _ => Err("expected three numbers".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(source: &str) -> error::Result<Mesh> {
+        parse(
+            source,
+            Path::new("test.obj"),
+            Material::lambertian(0.5, 0.5, 0.5),
+        )
+    }
+
+    const SQUARE: &str = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+";
+
+    #[test]
+    fn triangulates_polygons_as_a_fan() {
+        let mesh = parse_str(&format!("{}f 1 2 3 4\n", SQUARE)).unwrap();
+
+        let faces: Vec<_> = mesh.faces().iter().map(Face::positions).collect();
+        assert_eq!(faces, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn resolves_negative_indices_from_the_end() {
+        let mesh = parse_str(&format!("{}f -4 -3 -2\nv 2 2 0\nf -1 -2 -3\n", SQUARE)).unwrap();
+
+        let faces: Vec<_> = mesh.faces().iter().map(Face::positions).collect();
+        assert_eq!(faces, vec![[0, 1, 2], [4, 3, 2]]);
+    }
+
+    #[test]
+    fn reads_every_face_vertex_form() {
+        let source = format!(
+            "{}vt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 1\n\
+             f 1 2 3\nf 1/1 2/2 3/3\nf 1//1 2//1 3//1\nf 1/1/1 2/2/1 3/3/1\n",
+            SQUARE
+        );
+        let mesh = parse_str(&source).unwrap();
+
+        let attributes: Vec<_> = mesh
+            .faces()
+            .iter()
+            .map(|face| (face.uvs(), face.normals()))
+            .collect();
+        assert_eq!(
+            attributes,
+            vec![
+                (None, None),
+                (Some([0, 1, 2]), None),
+                (None, Some([0, 0, 0])),
+                (Some([0, 1, 2]), Some([0, 0, 0])),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices_with_the_line() {
+        match parse_str(&format!("{}f 1 2 5\n", SQUARE)) {
+            Err(Error::Parse { line, message, .. }) => {
+                assert_eq!(line, 5);
+                assert!(message.contains("out of range"), "{}", message);
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_faces_with_too_few_vertices() {
+        let result = parse_str(&format!("{}f 1 2\n", SQUARE));
+        assert!(matches!(result, Err(Error::Parse { line: 5, .. })));
+    }
+
+    #[test]
+    fn rejects_unknown_materials_with_the_line() {
+        match parse_str(&format!("{}usemtl missing\nf 1 2 3\n", SQUARE)) {
+            Err(Error::Parse { line, message, .. }) => {
+                assert_eq!(line, 5);
+                assert!(message.contains("missing"), "{}", message);
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_a_missing_mtl_library() {
+        let result = parse_str("mtllib does_not_exist.mtl\n");
+        assert!(matches!(result, Err(Error::Io { .. })));
+    }
+}
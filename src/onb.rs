@@ -0,0 +1,33 @@
+use crate::vec3::Vec3;
+
+/// Orthonormal basis built around a single direction, used to turn directions
+/// sampled around the Z axis into directions around that one.
+#[derive(Clone, Debug)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    pub fn from_w(w: &Vec3) -> Self {
+        let w = w.unit();
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(&a).into_unit();
+        let u = w.cross(&v);
+
+        Self { u, v, w }
+    }
+
+    pub fn w(&self) -> &Vec3 {
+        &self.w
+    }
+
+    pub fn local(&self, a: &Vec3) -> Vec3 {
+        a.x() * &self.u + a.y() * &self.v + a.z() * &self.w
+    }
+}
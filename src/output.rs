@@ -0,0 +1,189 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use image::{codecs::hdr::HdrEncoder, ColorType, ImageFormat, Rgb};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    exr, ppm,
+    tonemap::{self, ToneMap},
+    vec3::Vec3,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileType {
+    Ppm,
+    Png,
+    #[serde(alias = "jpg")]
+    Jpeg,
+    Bmp,
+    Tga,
+    Hdr,
+    Exr,
+}
+
+impl FileType {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+
+        let file_type = match extension.as_str() {
+            "ppm" => FileType::Ppm,
+            "png" => FileType::Png,
+            "jpg" | "jpeg" => FileType::Jpeg,
+            "bmp" => FileType::Bmp,
+            "tga" => FileType::Tga,
+            "hdr" => FileType::Hdr,
+            "exr" => FileType::Exr,
+            _ => return None,
+        };
+
+        Some(file_type)
+    }
+}
+
+/// Writes the linear radiance of each pixel, top row first, as `file_type`.
+///
+/// Without a file type it's inferred from the extension, falling back to PPM.
+/// HDR and EXR files keep the radiance as is, while the 8-bit formats go through
+/// `tone_map` first.
+pub fn create<P: AsRef<Path>>(
+    output_path: P,
+    file_type: Option<FileType>,
+    tone_map: ToneMap,
+    exposure: f64,
+    image_x: usize,
+    image_y: usize,
+    pixels: &[Vec3],
+) -> Result<()> {
+    let output_path = output_path.as_ref();
+    let io_error = |e| Error::io(output_path, e);
+    let file_type = file_type
+        .or_else(|| FileType::from_path(output_path))
+        .unwrap_or(FileType::Ppm);
+
+    let image_format = match file_type {
+        FileType::Ppm => {
+            let bytes = to_rgb8(pixels, tone_map, exposure);
+            return ppm::create(output_path, image_x, image_y, &bytes).map_err(io_error);
+        }
+        FileType::Exr => {
+            return exr::create(output_path, image_x, image_y, pixels).map_err(io_error);
+        }
+        FileType::Hdr => {
+            let pixels: Vec<Rgb<f32>> = pixels
+                .iter()
+                .map(|pixel| Rgb([pixel.r() as f32, pixel.g() as f32, pixel.b() as f32]))
+                .collect();
+
+            let output = BufWriter::new(File::create(output_path).map_err(io_error)?);
+            return HdrEncoder::new(output)
+                .encode(&pixels, image_x, image_y)
+                .map_err(|e| Error::image(output_path, e));
+        }
+        FileType::Png => ImageFormat::Png,
+        FileType::Jpeg => ImageFormat::Jpeg,
+        FileType::Bmp => ImageFormat::Bmp,
+        FileType::Tga => ImageFormat::Tga,
+    };
+
+    image::save_buffer_with_format(
+        output_path,
+        &to_rgb8(pixels, tone_map, exposure),
+        image_x as u32,
+        image_y as u32,
+        ColorType::Rgb8,
+        image_format,
+    )
+    .map_err(|e| Error::image(output_path, e))
+}
+
+/// Tone maps and sRGB encodes linear radiance into 8-bit RGB.
+pub fn to_rgb8(pixels: &[Vec3], tone_map: ToneMap, exposure: f64) -> Vec<u8> {
+    let quantize = |channel: f64| (tonemap::srgb_encode(channel) * 255.0).round() as u8;
+
+    pixels
+        .iter()
+        .flat_map(|pixel| {
+            let mapped = tone_map.map(pixel, exposure);
+            vec![
+                quantize(mapped.r()),
+                quantize(mapped.g()),
+                quantize(mapped.b()),
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, io::BufReader};
+
+    use image::codecs::hdr::HdrDecoder;
+
+    use super::*;
+
+    #[test]
+    fn file_types_come_from_the_extension() {
+        assert_eq!(FileType::from_path("out/render.png"), Some(FileType::Png));
+        assert_eq!(FileType::from_path("render.jpg"), Some(FileType::Jpeg));
+        assert_eq!(FileType::from_path("render.jpeg"), Some(FileType::Jpeg));
+        assert_eq!(FileType::from_path("render.hdr"), Some(FileType::Hdr));
+        assert_eq!(FileType::from_path("render.exr"), Some(FileType::Exr));
+        assert_eq!(FileType::from_path("render.ppm"), Some(FileType::Ppm));
+    }
+
+    #[test]
+    fn extensions_ignore_case() {
+        assert_eq!(FileType::from_path("RENDER.EXR"), Some(FileType::Exr));
+        assert_eq!(FileType::from_path("render.Png"), Some(FileType::Png));
+        assert_eq!(FileType::from_path("render.JPG"), Some(FileType::Jpeg));
+    }
+
+    #[test]
+    fn unknown_extensions_have_no_file_type() {
+        assert_eq!(FileType::from_path("render.gif"), None);
+        assert_eq!(FileType::from_path("render"), None);
+        assert_eq!(FileType::from_path("renders.d/render"), None);
+        assert_eq!(FileType::from_path(".png"), None);
+    }
+
+    #[test]
+    fn unknown_extensions_fall_back_to_ppm() {
+        let path = env::temp_dir().join("ray_tracer_output_fallback.img");
+        let pixels = [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0)];
+        create(&path, None, ToneMap::Clamp, 0.0, 2, 1, &pixels).unwrap();
+
+        let ppm = fs::read_to_string(&path).unwrap();
+        assert_eq!(ppm, "P3\n2 1\n255\n255 0 0\n0 0 0\n");
+    }
+
+    #[test]
+    fn hdr_keeps_radiance_past_one() {
+        let path = env::temp_dir().join("ray_tracer_output_round_trip.hdr");
+        let pixels = [
+            Vec3::new(0.5, 1.0, 2.0),
+            Vec3::new(16.0, 0.25, 0.0),
+            Vec3::new(1000.0, 3.0, 0.125),
+            Vec3::new(0.0, 0.0, 0.0),
+        ];
+        create(&path, None, ToneMap::Clamp, 0.0, 2, 2, &pixels).unwrap();
+
+        let file = BufReader::new(fs::File::open(&path).unwrap());
+        let decoded = HdrDecoder::new(file).unwrap().read_image_hdr().unwrap();
+        assert_eq!(decoded.len(), pixels.len());
+        for (decoded, pixel) in decoded.iter().zip(pixels.iter()) {
+            let expected = [pixel.r(), pixel.g(), pixel.b()];
+            let brightest = expected.iter().cloned().fold(0.0, f64::max);
+            for (&channel, &expected) in decoded.0.iter().zip(expected.iter()) {
+                // RGBE shares one exponent between the channels of a pixel
+                assert!(
+                    (channel as f64 - expected).abs() <= brightest / 128.0,
+                    "{} decoded as {}",
+                    expected,
+                    channel
+                );
+            }
+        }
+    }
+}
@@ -28,7 +28,9 @@ impl Perlin {
         }
 
         Self {
-            random_vecs: unsafe { mem::transmute(random_vecs) },
+            random_vecs: unsafe {
+                mem::transmute::<[MaybeUninit<Vec3>; POINT_COUNT], [Vec3; POINT_COUNT]>(random_vecs)
+            },
             perm_x: generate_perm(rng),
             perm_y: generate_perm(rng),
             perm_z: generate_perm(rng),
@@ -81,7 +83,12 @@ impl Perlin {
             }
         }
 
-        trilinear_interp(unsafe { mem::transmute(sample) }, u, v, w)
+        trilinear_interp(
+            unsafe { mem::transmute::<[[[MaybeUninit<Vec3>; 2]; 2]; 2], TrilinearSample>(sample) },
+            u,
+            v,
+            w,
+        )
     }
 }
 
@@ -97,6 +104,7 @@ fn generate_perm<T: Rng>(rng: &mut T) -> [i32; POINT_COUNT] {
     p
 }
 
+#[allow(clippy::needless_range_loop)]
 fn trilinear_interp(sample: TrilinearSample, u: f64, v: f64, w: f64) -> f64 {
     let uu = u * u * (3.0 - 2.0 * u);
     let vv = v * v * (3.0 - 2.0 * v);
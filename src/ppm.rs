@@ -1,9 +1,14 @@
 use core::fmt::Write as FmtWrite;
-use std::{fs::File, io::Write, path::Path};
+use std::{fs::File, io, io::Write, path::Path};
 
 use itertools::Itertools;
 
-pub fn create<P: AsRef<Path>>(output_path: P, image_x: usize, image_y: usize, bytes: &[u8]) {
+pub fn create<P: AsRef<Path>>(
+    output_path: P,
+    image_x: usize,
+    image_y: usize,
+    bytes: &[u8],
+) -> io::Result<()> {
     let mut buffer = String::new();
     for row in bytes.chunks(3).chunks(image_x).into_iter() {
         for pixel in row {
@@ -11,17 +16,16 @@ pub fn create<P: AsRef<Path>>(output_path: P, image_x: usize, image_y: usize, by
             let g = pixel[1];
             let b = pixel[2];
 
-            buffer.write_str(&format!("{} {} {}\n", r, g, b));
+            writeln!(buffer, "{} {} {}", r, g, b).expect("writing to a String cannot fail");
         }
     }
 
-    let mut output = File::create(output_path).unwrap();
-    output.write_all(&[]);
+    let mut output = File::create(output_path)?;
 
     {
         let header = format!("P3\n{} {}\n255\n", image_x, image_y);
-        output.write(header.as_bytes());
+        output.write_all(header.as_bytes())?;
     }
 
-    output.write(buffer.as_bytes());
+    output.write_all(buffer.as_bytes())
 }
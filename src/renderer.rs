@@ -1,13 +1,164 @@
+use std::path::Path;
+
 use rand::{rngs::SmallRng, Rng, SeedableRng};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::{
+    iter::{IntoParallelIterator, ParallelIterator},
+    ThreadPoolBuilder,
+};
 
 use crate::{
-    bvh::BoundingVolumeHierarchy, camera::Camera, hittable::Hittable, ray::Ray, util::RandomDouble,
+    background::Background,
+    bvh::BoundingVolumeHierarchy,
+    camera::Camera,
+    error::Result,
+    hittable::{Hit, Hittable},
+    light::Lights,
+    output::{self, FileType},
+    ray::Ray,
+    scene::Scene,
+    tonemap::ToneMap,
+    util::RandomDouble,
     vec3::Vec3,
 };
 
-pub fn render_world<'a>(
-    background: &Vec3,
+/// Path traces scenes into a `Framebuffer` of linear radiance.
+#[derive(Clone, Debug)]
+pub struct Renderer {
+    width: usize,
+    height: usize,
+    samples: usize,
+    parallel: bool,
+    use_bounding_volume: bool,
+    seed: u64,
+    threads: Option<usize>,
+}
+
+impl Renderer {
+    /// Renders take 100 samples per pixel, in parallel and through the BVH.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            samples: 100,
+            parallel: true,
+            use_bounding_volume: true,
+            seed: 0,
+            threads: None,
+        }
+    }
+
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Without it every ray is tested against every shape, which is only useful
+    /// for checking the BVH.
+    pub fn use_bounding_volume(mut self, use_bounding_volume: bool) -> Self {
+        self.use_bounding_volume = use_bounding_volume;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// How many threads a parallel render uses, instead of one per core.
+    pub fn threads(mut self, threads: Option<usize>) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub fn render(&self, scene: &Scene, camera: &Camera) -> Framebuffer {
+        let render = || {
+            render_world(
+                scene.background(),
+                scene.world(),
+                camera,
+                self.width,
+                self.height,
+                self.samples,
+                self.parallel,
+                self.use_bounding_volume,
+                self.seed,
+            )
+        };
+
+        // Falls back to the global pool if the threads can't be spawned
+        let pool = self
+            .threads
+            .and_then(|threads| ThreadPoolBuilder::new().num_threads(threads).build().ok());
+        let pixels = match pool {
+            Some(pool) => pool.install(render),
+            None => render(),
+        };
+
+        Framebuffer {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+}
+
+/// Linear radiance for every pixel, in rows starting from the top left.
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+}
+
+impl Framebuffer {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Vec3] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> &Vec3 {
+        &self.pixels[y * self.width + x]
+    }
+
+    /// Tone mapped 8-bit RGB, as written to the LDR formats.
+    pub fn to_rgb8(&self, tone_map: ToneMap, exposure: f64) -> Vec<u8> {
+        output::to_rgb8(&self.pixels, tone_map, exposure)
+    }
+
+    /// See `output::create` for how the file type and tone mapping are applied.
+    pub fn save<P: AsRef<Path>>(
+        &self,
+        path: P,
+        file_type: Option<FileType>,
+        tone_map: ToneMap,
+        exposure: f64,
+    ) -> Result<()> {
+        output::create(
+            path,
+            file_type,
+            tone_map,
+            exposure,
+            self.width,
+            self.height,
+            &self.pixels,
+        )
+    }
+}
+
+fn render_world<'a>(
+    background: &Background,
     world: &'a BoundingVolumeHierarchy,
     camera: &Camera,
     screen_width: usize,
@@ -15,8 +166,10 @@ pub fn render_world<'a>(
     antialias_iterations: usize,
     render_parallel: bool,
     use_bounding_volume: bool,
-) -> Vec<u8> {
+    seed: u64,
+) -> Vec<Vec3> {
     let screen = 0..(screen_width * screen_height);
+    let lights = Lights::new(world, background);
 
     let world: &'a dyn Hittable<'a, _> = if use_bounding_volume {
         world
@@ -27,11 +180,13 @@ pub fn render_world<'a>(
     if render_parallel {
         screen
             .into_par_iter()
-            .map_init(SmallRng::from_entropy, |rng, idx| {
+            .map(|idx| {
+                let mut rng = pixel_rng(seed, idx);
                 render(
-                    rng,
+                    &mut rng,
                     background,
                     world,
+                    &lights,
                     camera,
                     screen_width,
                     screen_height,
@@ -39,16 +194,16 @@ pub fn render_world<'a>(
                     idx,
                 )
             })
-            .flat_map(|pixel| vec![pixel.r() as u8, pixel.g() as u8, pixel.b() as u8])
             .collect()
     } else {
         screen
             .map(|idx| {
-                let mut rng = SmallRng::from_entropy();
+                let mut rng = pixel_rng(seed, idx);
                 render(
                     &mut rng,
                     background,
                     world,
+                    &lights,
                     camera,
                     screen_width,
                     screen_height,
@@ -56,15 +211,26 @@ pub fn render_world<'a>(
                     idx,
                 )
             })
-            .flat_map(|pixel| vec![pixel.r() as u8, pixel.g() as u8, pixel.b() as u8])
             .collect()
     }
 }
 
+/// Each pixel gets its own stream, so the result doesn't depend on which thread
+/// renders it or in what order.
+fn pixel_rng(seed: u64, idx: usize) -> SmallRng {
+    // SplitMix64, so neighbouring pixels don't end up with similar seeds
+    let mut z = seed.wrapping_add((idx as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+    SmallRng::seed_from_u64(z ^ (z >> 31))
+}
+
 fn render<'a, T: Rng>(
     rng: &mut T,
-    background: &Vec3,
+    background: &Background,
     world: &'a dyn Hittable<'a, T>,
+    lights: &Lights<'_>,
     camera: &Camera,
     screen_width: usize,
     screen_height: usize,
@@ -79,37 +245,136 @@ fn render<'a, T: Rng>(
         let u = (i as f64 + rng.random_double()) / (screen_width as f64);
         let v = (j as f64 + rng.random_double()) / (screen_height as f64);
 
-        let ray = camera.ray(rng, u, v);
-        pixel += color(rng, &ray, background, world, 50);
+        if let Some(ray) = camera.ray(rng, u, v) {
+            pixel += color(rng, &ray, background, world, lights, None, 50);
+        }
     }
     pixel /= antialias_iterations as f64;
-    pixel = Vec3::new(pixel.r().sqrt(), pixel.g().sqrt(), pixel.b().sqrt());
-    pixel *= 255.99;
 
     pixel
 }
 
+/// `bsdf_pdf` is the density the previous bounce sampled `ray` with, or `None`
+/// for camera rays and specular bounces that light sampling can't reproduce.
 fn color<'a, T: Rng>(
     rng: &mut T,
     ray: &Ray,
-    background: &Vec3,
+    background: &Background,
     world: &'a dyn Hittable<'a, T>,
+    lights: &Lights<'_>,
+    bsdf_pdf: Option<f64>,
     max_depth: u8,
 ) -> Vec3 {
     if max_depth == 0 {
         return Vec3::new(0.0, 0.0, 0.0);
     }
 
-    if let Some(hit) = world.hit(ray, 0.001, std::f64::INFINITY) {
-        let emitted = hit.material().emit(hit.u(), hit.v(), hit.p());
+    let hit = match world.hit(ray, 0.001, f64::INFINITY, rng) {
+        Some(hit) => hit,
+        None => {
+            let mut background = background.value(ray.direction());
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                // The environment map is one of the lights, so this gets split as well
+                background *= power_heuristic(bsdf_pdf, lights.pdf(ray));
+            }
 
-        return if let Some(scatter) = hit.material().scatter(rng, ray, &hit) {
-            (emitted + scatter.attenuation())
-                * color(rng, scatter.scattered(), background, world, max_depth - 1)
-        } else {
-            emitted
-        };
+            return background;
+        }
+    };
+
+    let mut emitted = hit.material().emit(hit.u(), hit.v(), hit.p());
+    if let Some(bsdf_pdf) = bsdf_pdf {
+        // Light sampling could have found this too, so it only gets its MIS share
+        emitted *= power_heuristic(bsdf_pdf, lights.pdf(ray));
     }
 
-    background.clone()
+    let scatter = match hit.material().scatter(rng, ray, &hit) {
+        Some(scatter) => scatter,
+        None => return emitted,
+    };
+
+    let attenuation = scatter.attenuation().clone();
+    let scattered = scatter.scattered();
+
+    if scatter.is_specular() {
+        return emitted
+            + attenuation
+                * color(
+                    rng,
+                    scattered,
+                    background,
+                    world,
+                    lights,
+                    None,
+                    max_depth - 1,
+                );
+    }
+
+    let direct = sample_lights(rng, ray, &hit, &attenuation, background, world, lights);
+    if scatter.pdf() <= 0.0 {
+        return emitted + direct;
+    }
+
+    let scattering_pdf = hit.material().scattering_pdf(ray, &hit, scattered);
+    let indirect = attenuation
+        * (scattering_pdf / scatter.pdf())
+        * color(
+            rng,
+            scattered,
+            background,
+            world,
+            lights,
+            Some(scatter.pdf()),
+            max_depth - 1,
+        );
+
+    emitted + direct + indirect
+}
+
+/// Radiance arriving at `hit` from a direction picked by sampling the lights,
+/// weighted against the chance of the material scattering that way itself.
+fn sample_lights<'a, T: Rng>(
+    rng: &mut T,
+    ray: &Ray,
+    hit: &Hit<'_, T>,
+    attenuation: &Vec3,
+    background: &Background,
+    world: &'a dyn Hittable<'a, T>,
+    lights: &Lights<'_>,
+) -> Vec3 {
+    if lights.is_empty() {
+        return Vec3::default();
+    }
+
+    let direction = match lights.sample(rng, hit.p()) {
+        Some(direction) => direction,
+        None => return Vec3::default(),
+    };
+
+    let shadow_ray = Ray::new(hit.p().clone(), direction, ray.time());
+    let light_pdf = lights.pdf(&shadow_ray);
+    let scattering_pdf = hit.material().scattering_pdf(ray, hit, &shadow_ray);
+    if light_pdf <= 0.0 || scattering_pdf <= 0.0 {
+        return Vec3::default();
+    }
+
+    // Smoke along the way dims the light rather than blocking it outright
+    let (emitted, distance) = match world.surface_hit(&shadow_ray, 0.001, f64::INFINITY, rng) {
+        Some(light_hit) => (
+            light_hit
+                .material()
+                .emit(light_hit.u(), light_hit.v(), light_hit.p()),
+            light_hit.t(),
+        ),
+        None => (background.value(shadow_ray.direction()), f64::INFINITY),
+    };
+    let emitted = emitted * world.transmittance(&shadow_ray, 0.001, distance, rng);
+    let weight = power_heuristic(light_pdf, scattering_pdf);
+
+    emitted * attenuation.clone() * (scattering_pdf / light_pdf * weight)
+}
+
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let pdf_squared = pdf * pdf;
+    pdf_squared / (pdf_squared + other_pdf * other_pdf)
 }
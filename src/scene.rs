@@ -0,0 +1,153 @@
+use rand::{rngs::SmallRng, SeedableRng};
+
+use crate::{
+    background::Background,
+    bvh::{BoundingVolumeHierarchy, SplitMethod},
+    config::TracerConfig,
+    error::{Error, Result},
+    hittable::Shape,
+    world::{gen_background, gen_world},
+};
+
+/// Everything a camera can see: the shapes, already in a BVH, and what's behind them.
+#[derive(Debug)]
+pub struct Scene {
+    world: BoundingVolumeHierarchy,
+    background: Background,
+    split_method: SplitMethod,
+    seed: u64,
+}
+
+impl Scene {
+    pub fn builder() -> SceneBuilder {
+        SceneBuilder::default()
+    }
+
+    /// Builds the world preset or custom scene described by `config`.
+    pub fn from_config(config: &TracerConfig, seed: u64) -> Result<Self> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+
+        let split_method = config.renderer_config().bvh_split();
+        let time_start = config.camera_config().time_start();
+        let time_end = config.camera_config().time_end();
+        if !(time_start.is_finite() && time_end.is_finite()) {
+            let message = format!(
+                "Camera shutter times {} to {} must be finite",
+                time_start, time_end
+            );
+            return Err(Error::scene(message));
+        }
+
+        let shapes = gen_world(
+            &mut rng,
+            config.world_config(),
+            config.scene_config(),
+            split_method,
+            time_start,
+            time_end,
+        )?;
+
+        let scene = Self::builder()
+            .shapes(shapes)
+            .background(gen_background(config.background_config())?)
+            .split_method(split_method)
+            .time(time_start, time_end)
+            .seed(seed)
+            .build();
+
+        Ok(scene)
+    }
+
+    /// Bounds the world over a new shutter interval, for rendering another frame
+    /// of an animation without building the shapes again. The BVH is refitted
+    /// unless that makes it too slow to trace, and then it's rebuilt.
+    pub fn set_time(&mut self, time_start: f64, time_end: f64) {
+        let mut rng = SmallRng::seed_from_u64(self.seed);
+        self.world
+            .update(&mut rng, self.split_method, time_start, time_end);
+    }
+
+    pub fn world(&self) -> &BoundingVolumeHierarchy {
+        &self.world
+    }
+
+    pub fn background(&self) -> &Background {
+        &self.background
+    }
+}
+
+#[derive(Debug)]
+pub struct SceneBuilder {
+    shapes: Vec<Shape>,
+    background: Background,
+    split_method: SplitMethod,
+    time_start: f64,
+    time_end: f64,
+    seed: u64,
+}
+
+impl SceneBuilder {
+    pub fn shape(mut self, shape: Shape) -> Self {
+        self.shapes.push(shape);
+        self
+    }
+
+    pub fn shapes<I: IntoIterator<Item = Shape>>(mut self, shapes: I) -> Self {
+        self.shapes.extend(shapes);
+        self
+    }
+
+    pub fn background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub fn split_method(mut self, split_method: SplitMethod) -> Self {
+        self.split_method = split_method;
+        self
+    }
+
+    /// The shutter interval, which moving shapes are bounded over.
+    pub fn time(mut self, time_start: f64, time_end: f64) -> Self {
+        self.time_start = time_start;
+        self.time_end = time_end;
+        self
+    }
+
+    /// Only matters for `SplitMethod::Median`, which splits along random axes.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn build(self) -> Scene {
+        let mut rng = SmallRng::seed_from_u64(self.seed);
+        let world = BoundingVolumeHierarchy::new(
+            &mut rng,
+            self.shapes,
+            self.split_method,
+            self.time_start,
+            self.time_end,
+        );
+
+        Scene {
+            world,
+            background: self.background,
+            split_method: self.split_method,
+            seed: self.seed,
+        }
+    }
+}
+
+impl Default for SceneBuilder {
+    fn default() -> Self {
+        Self {
+            shapes: Vec::new(),
+            background: Background::default(),
+            split_method: SplitMethod::default(),
+            time_start: 0.0,
+            time_end: 1.0,
+            seed: 0,
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use crate::{perlin::Perlin, vec3::Vec3};
+use crate::{
+    error::{Error, Result},
+    perlin::Perlin,
+    vec3::Vec3,
+};
 use image::{self, DynamicImage, GenericImageView};
 use rand::Rng;
 use std::{
@@ -81,7 +85,7 @@ impl NoiseTexture {
 
 impl Texturable for NoiseTexture {
     fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Vec3 {
-        let turbulence = self.scale * p.z() + 10.0 * self.noise.turbulence(&p, 7);
+        let turbulence = self.scale * p.z() + 10.0 * self.noise.turbulence(p, 7);
         Vec3::new(1.0, 1.0, 1.0) * 0.5 * (1.0 + turbulence.sin())
     }
 }
@@ -104,7 +108,7 @@ impl Debug for ImageTexture {
 }
 
 impl Texturable for ImageTexture {
-    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+    fn value(&self, u: f64, v: f64, _p: &Vec3) -> Vec3 {
         let u = fclamp(u, 0.0, 1.0);
         let v = 1.0 - fclamp(v, 0.0, 1.0); // Flip V to image coords
 
@@ -166,18 +170,11 @@ impl Texture {
         Self::Noise(texture)
     }
 
-    pub fn image<P: AsRef<Path>>(filepath: P) -> Self {
+    pub fn image<P: AsRef<Path>>(filepath: P) -> Result<Self> {
         let filepath = filepath.as_ref();
-        let texture = match image::open(filepath) {
-            Ok(texture) => texture,
-            // The file was not found, log and return an error texture
-            Err(e) => {
-                eprintln!("Unable to load image {:?}: {}", filepath, e);
-                return Texture::constant(0.0, 1.0, 1.0);
-            }
-        };
+        let texture = image::open(filepath).map_err(|e| Error::image(filepath, e))?;
         let texture = ImageTexture::new(texture);
-        Self::Image(texture)
+        Ok(Self::Image(texture))
     }
 }
 
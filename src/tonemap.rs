@@ -0,0 +1,144 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::vec3::Vec3;
+
+/// Maps linear radiance, which can be arbitrarily bright, into the 0-1 range an
+/// 8-bit image can hold.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMap {
+    /// Cuts off anything brighter than 1.
+    #[default]
+    Clamp,
+    /// Compresses luminance with `L / (1 + L)`, keeping the hue of bright colors.
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve.
+    Aces,
+}
+
+impl ToneMap {
+    /// Scales `color` by `2^exposure` before mapping it.
+    pub fn map(self, color: &Vec3, exposure: f64) -> Vec3 {
+        let color = exposure.exp2() * color;
+
+        let mapped = match self {
+            ToneMap::Clamp => color,
+            ToneMap::Reinhard => {
+                let luminance = 0.2126 * color.r() + 0.7152 * color.g() + 0.0722 * color.b();
+                if luminance <= 0.0 {
+                    return Vec3::default();
+                }
+
+                (1.0 / (1.0 + luminance)) * color
+            }
+            ToneMap::Aces => {
+                let curve = |x: f64| {
+                    // The fit is made for values already scaled down by this much
+                    let x = 0.6 * x.max(0.0);
+                    (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+                };
+
+                Vec3::new(curve(color.r()), curve(color.g()), curve(color.b()))
+            }
+        };
+
+        let clamp = |x: f64| x.clamp(0.0, 1.0);
+        Vec3::new(clamp(mapped.r()), clamp(mapped.g()), clamp(mapped.b()))
+    }
+}
+
+/// The sRGB transfer function, taking a linear value in 0-1 to an encoded one.
+pub fn srgb_encode(linear: f64) -> f64 {
+    if linear <= 0.003_130_8 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TONE_MAPS: [ToneMap; 3] = [ToneMap::Clamp, ToneMap::Reinhard, ToneMap::Aces];
+
+    fn gray(value: f64) -> Vec3 {
+        Vec3::new(value, value, value)
+    }
+
+    #[test]
+    fn black_stays_black() {
+        for &tone_map in &TONE_MAPS {
+            assert_eq!(tone_map.map(&gray(0.0), 0.0), gray(0.0));
+            assert_eq!(tone_map.map(&gray(0.0), 4.0), gray(0.0));
+        }
+    }
+
+    #[test]
+    fn curves_rise_with_radiance() {
+        for &tone_map in &TONE_MAPS {
+            let mut previous = 0.0;
+            for step in 1..=1000 {
+                let value = tone_map.map(&gray(step as f64 * 0.01), 0.0).r();
+                assert!(value >= previous, "{:?} falls at {}", tone_map, step);
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn bright_colors_stay_in_range() {
+        let colors = [
+            gray(1e6),
+            Vec3::new(1e6, 0.0, 0.0),
+            Vec3::new(50.0, 1.0, 0.1),
+            Vec3::new(-1.0, 2.0, 0.5),
+        ];
+        for &tone_map in &TONE_MAPS {
+            for color in colors.iter() {
+                let mapped = tone_map.map(color, 0.0);
+                for &channel in &[mapped.r(), mapped.g(), mapped.b()] {
+                    assert!((0.0..=1.0).contains(&channel), "{:?} {:?}", tone_map, color);
+                }
+            }
+        }
+
+        // Reinhard never quite reaches white, while ACES saturates
+        assert!(ToneMap::Reinhard.map(&gray(1e6), 0.0).r() < 1.0);
+        assert_eq!(ToneMap::Aces.map(&gray(1e6), 0.0).r(), 1.0);
+    }
+
+    #[test]
+    fn clamp_cuts_off_at_one() {
+        let mapped = ToneMap::Clamp.map(&Vec3::new(0.5, 2.0, -1.0), 0.0);
+        assert_eq!(mapped, Vec3::new(0.5, 1.0, 0.0));
+    }
+
+    #[test]
+    fn exposure_doubles_per_stop() {
+        let color = Vec3::new(0.1, 0.2, 0.4);
+        for &tone_map in &TONE_MAPS {
+            let exposed = tone_map.map(&color, 1.0);
+            let doubled = tone_map.map(&(2.0 * &color), 0.0);
+            assert!((exposed - doubled).length() < 1e-12, "{:?}", tone_map);
+        }
+
+        let halved = ToneMap::Clamp.map(&color, -1.0);
+        assert!((halved - Vec3::new(0.05, 0.1, 0.2)).length() < 1e-12);
+    }
+
+    #[test]
+    fn srgb_meets_at_the_breakpoint() {
+        assert_eq!(srgb_encode(0.0), 0.0);
+        assert!((srgb_encode(1.0) - 1.0).abs() < 1e-12);
+
+        let breakpoint = 0.003_130_8;
+        let linear_part = 12.92 * breakpoint;
+        let gamma_part = srgb_encode(breakpoint + 1e-12);
+        assert!((linear_part - 0.040_45).abs() < 1e-5);
+        assert!((gamma_part - linear_part).abs() < 1e-6);
+
+        // Middle gray
+        assert!((srgb_encode(0.18) - 0.461_356).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,294 @@
+use std::ops::Mul;
+
+use crate::{aabb::AABB, ray::Ray, vec3::Vec3};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix4 {
+    m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub fn identity() -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translation(offset: &Vec3) -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factors: &Vec3) -> Self {
+        Self::new([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_x(degrees: f64) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+
+        Self::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, cos, -sin, 0.0],
+            [0.0, sin, cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_y(degrees: f64) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+
+        Self::new([
+            [cos, 0.0, sin, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [-sin, 0.0, cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_z(degrees: f64) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+
+        Self::new([
+            [cos, -sin, 0.0, 0.0],
+            [sin, cos, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting, `None` if the matrix is
+    /// singular or isn't all finite.
+    pub fn inverse(&self) -> Option<Self> {
+        if self.m.iter().flatten().any(|value| !value.is_finite()) {
+            return None;
+        }
+
+        let mut a = self.m;
+        let mut inverse = Self::identity().m;
+
+        for column in 0..4 {
+            let pivot =
+                (column..4).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))?;
+
+            if a[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+
+            a.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = 1.0 / a[column][column];
+            for k in 0..4 {
+                a[column][k] *= scale;
+                inverse[column][k] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == column {
+                    continue;
+                }
+
+                let factor = a[row][column];
+                for k in 0..4 {
+                    a[row][k] -= factor * a[column][k];
+                    inverse[row][k] -= factor * inverse[column][k];
+                }
+            }
+        }
+
+        Some(Self::new(inverse))
+    }
+
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        let m = &self.m;
+
+        let x = m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3];
+        let y = m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3];
+        let z = m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3];
+
+        Vec3::new(x, y, z)
+    }
+
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+
+        let x = m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z();
+        let y = m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z();
+        let z = m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z();
+
+        Vec3::new(x, y, z)
+    }
+}
+
+impl Mul for &Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+
+        Matrix4::new(m)
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+/// Affine object-to-world transform, stored alongside its inverse so rays can be
+/// brought into object space without inverting per hit test.
+#[derive(Clone, Debug)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Transform {
+    /// `None` if `matrix` can't be inverted, such as when it flattens space.
+    pub fn new(matrix: Matrix4) -> Option<Self> {
+        let inverse = matrix.inverse()?;
+        Some(Self { matrix, inverse })
+    }
+
+    /// Scales, then rotates about X, Y and Z (in degrees) and finally translates.
+    /// `None` if the result can't be inverted.
+    pub fn from_parts(translate: &Vec3, rotate: &Vec3, scale: &Vec3) -> Option<Self> {
+        let matrix = Matrix4::translation(translate)
+            * Matrix4::rotation_z(rotate.z())
+            * Matrix4::rotation_y(rotate.y())
+            * Matrix4::rotation_x(rotate.x())
+            * Matrix4::scaling(scale);
+
+        Self::new(matrix)
+    }
+
+    /// Rotates about X, Y and Z (in degrees) around `pivot`, then translates. The
+    /// inverse is built directly, which is cheap enough to do per ray.
+    pub fn rigid(translate: &Vec3, rotate: &Vec3, pivot: &Vec3) -> Self {
+        let matrix = Matrix4::translation(&(pivot + translate))
+            * Matrix4::rotation_z(rotate.z())
+            * Matrix4::rotation_y(rotate.y())
+            * Matrix4::rotation_x(rotate.x())
+            * Matrix4::translation(&-pivot.clone());
+        let inverse = Matrix4::translation(pivot)
+            * Matrix4::rotation_x(-rotate.x())
+            * Matrix4::rotation_y(-rotate.y())
+            * Matrix4::rotation_z(-rotate.z())
+            * Matrix4::translation(&-(pivot + translate));
+
+        Self { matrix, inverse }
+    }
+
+    /// The direction is left unnormalized so hit distances stay valid in world space.
+    pub fn ray_to_local(&self, ray: &Ray) -> Ray {
+        let origin = self.inverse.transform_point(ray.origin());
+        let direction = self.inverse.transform_vector(ray.direction());
+
+        Ray::new(origin, direction, ray.time())
+    }
+
+    pub fn point_to_world(&self, p: &Vec3) -> Vec3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn normal_to_world(&self, normal: &Vec3) -> Vec3 {
+        // Normals transform by the inverse transpose
+        let m = &self.inverse.m;
+
+        let x = m[0][0] * normal.x() + m[1][0] * normal.y() + m[2][0] * normal.z();
+        let y = m[0][1] * normal.x() + m[1][1] * normal.y() + m[2][1] * normal.z();
+        let z = m[0][2] * normal.x() + m[1][2] * normal.y() + m[2][2] * normal.z();
+
+        Vec3::new(x, y, z).into_unit()
+    }
+
+    pub fn bounding_box_to_world(&self, aabb: &AABB) -> AABB {
+        let (min, max) = (aabb.min(), aabb.max());
+
+        let mut corners = (0..8).map(|corner| {
+            let x = if corner & 1 == 0 { min.x() } else { max.x() };
+            let y = if corner & 2 == 0 { min.y() } else { max.y() };
+            let z = if corner & 4 == 0 { min.z() } else { max.z() };
+
+            self.point_to_world(&Vec3::new(x, y, z))
+        });
+
+        let first = corners.next().expect("a box has eight corners");
+        let initial = AABB::new(first.clone(), first);
+
+        corners.fold(initial, |aabb, corner| {
+            aabb.surrounding_box(&AABB::new(corner.clone(), corner))
+        })
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let transform = Transform::from_parts(
+            &Vec3::new(1.0, -2.0, 3.0),
+            &Vec3::new(30.0, 45.0, -60.0),
+            &Vec3::new(2.0, 0.5, 1.5),
+        )
+        .unwrap();
+
+        let p = Vec3::new(0.3, -0.7, 1.1);
+        let ray = Ray::new(transform.point_to_world(&p), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert!((transform.ray_to_local(&ray).origin() - &p).length() < 1e-9);
+    }
+
+    #[test]
+    fn flattening_transforms_have_no_inverse() {
+        let translate = Vec3::default();
+        let rotate = Vec3::new(0.0, 30.0, 0.0);
+
+        assert!(Transform::from_parts(&translate, &rotate, &Vec3::new(0.0, 1.0, 1.0)).is_none());
+        assert!(Transform::from_parts(&translate, &rotate, &Vec3::new(1e-13, 1.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn non_finite_matrices_have_no_inverse() {
+        let mut m = Matrix4::identity().m;
+        m[1][2] = f64::NAN;
+        assert!(Matrix4::new(m).inverse().is_none());
+
+        m[1][2] = f64::INFINITY;
+        assert!(Transform::new(Matrix4::new(m)).is_none());
+    }
+}
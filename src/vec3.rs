@@ -2,7 +2,8 @@ use core::fmt;
 use rand::Rng;
 use serde::{
     de::{self, MapAccess, Visitor},
-    Deserialize, Deserializer,
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
 };
 use std::{
     fmt::Formatter,
@@ -85,6 +86,10 @@ impl Vec3 {
         self
     }
 
+    pub fn is_finite(&self) -> bool {
+        self.x().is_finite() && self.y().is_finite() && self.z().is_finite()
+    }
+
     pub fn dot(&self, other: &Self) -> f64 {
         self.x() * other.x() + self.y() * other.y() + self.z() * other.z()
     }
@@ -143,6 +148,19 @@ impl<'de> Deserialize<'de> for Vec3 {
     }
 }
 
+impl Serialize for Vec3 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut vec = serializer.serialize_struct("Vec3", 3)?;
+        vec.serialize_field("x", &self.x())?;
+        vec.serialize_field("y", &self.y())?;
+        vec.serialize_field("z", &self.z())?;
+        vec.end()
+    }
+}
+
 impl Add for &Vec3 {
     type Output = Vec3;
 
@@ -1,28 +1,68 @@
+use std::{collections::HashMap, sync::Arc};
+
 use itertools::Itertools;
 use rand::{seq::SliceRandom, Rng};
 
 use crate::{
-    bvh::BoundingVolumeHierarchy, config::WorldConfig, hittable::Shape, material::Material,
-    texture::Texture, util::RandomDouble,
+    background::{Background, EnvironmentMap},
+    bvh::{BoundingVolumeHierarchy, SplitMethod},
+    config::{
+        BackgroundConfig, DensityFieldConfig, MaterialConfig, MotionConfig, ObjectConfig,
+        SceneConfig, ShapeConfig, TextureConfig, WorldConfig,
+    },
+    density::{DensityField, VoxelGrid},
+    error::{Error, Result},
+    hittable::{Face, Hittable, Keyframe, Mesh, Shape},
+    material::Material,
+    obj,
+    texture::Texture,
+    transform::Transform,
+    util::RandomDouble,
+    vec3::Vec3,
 };
 
 pub fn gen_world<T: Rng>(
     rng: &mut T,
     world_config: &WorldConfig,
+    scene_config: &SceneConfig,
+    split_method: SplitMethod,
     time_initial: f64,
     time_final: f64,
-) -> BoundingVolumeHierarchy {
+) -> Result<Vec<Shape>> {
     let world = match world_config {
         WorldConfig::Basic => static_world(),
         WorldConfig::Dynamic { max_objects } => random_world(rng, *max_objects),
         WorldConfig::Checker => two_checker_spheres(),
         WorldConfig::Perlin => two_perlin_spheres(rng),
-        WorldConfig::Earth => earth(),
+        WorldConfig::Earth => earth()?,
         WorldConfig::SimpleLight => simple_light(rng),
         WorldConfig::CornellBox => cornell_box(),
+        WorldConfig::CornellSmoke => cornell_smoke(),
+        WorldConfig::Custom => {
+            custom_world(rng, scene_config, split_method, time_initial, time_final)?
+        }
     };
 
-    BoundingVolumeHierarchy::new(rng, world, time_initial, time_final)
+    Ok(world)
+}
+
+pub fn gen_background(background_config: &BackgroundConfig) -> Result<Background> {
+    let background = match background_config {
+        BackgroundConfig::Constant { color } => Background::constant(color.clone()),
+        BackgroundConfig::Gradient { bottom, top } => {
+            Background::gradient(bottom.clone(), top.clone())
+        }
+        BackgroundConfig::Environment {
+            path,
+            rotation,
+            intensity,
+        } => {
+            let map = EnvironmentMap::open(path, *rotation, *intensity)?;
+            Background::environment(map)
+        }
+    };
+
+    Ok(background)
 }
 
 fn static_world() -> Vec<Shape> {
@@ -197,10 +237,10 @@ fn two_perlin_spheres<T: Rng>(rng: &mut T) -> Vec<Shape> {
     vec![ground_sphere, sphere]
 }
 
-fn earth() -> Vec<Shape> {
-    let texture = Texture::image("resources/earthmap.jpg");
+fn earth() -> Result<Vec<Shape>> {
+    let texture = Texture::image("resources/earthmap.jpg")?;
     let globe = Shape::sphere(0.0, 0.0, 0.0, 2.0, Material::textured(texture));
-    vec![globe]
+    Ok(vec![globe])
 }
 
 fn simple_light<T: Rng>(rng: &mut T) -> Vec<Shape> {
@@ -223,24 +263,689 @@ fn simple_light<T: Rng>(rng: &mut T) -> Vec<Shape> {
 }
 
 fn cornell_box() -> Vec<Shape> {
-    let red = Material::lambertian(0.65, 0.05, 0.05);
-    let green = Material::lambertian(0.12, 0.45, 0.15);
     let white = Material::lambertian(0.73, 0.73, 0.73);
     let light = Material::diffuse_light(Texture::constant(15.0, 15.0, 15.0));
 
+    let mut world = cornell_room(white.clone());
+    world.push(Shape::xz_rect(213.0, 343.0, 227.0, 332.0, 554.0, light));
+
+    let (short_box, tall_box) = cornell_blocks(white.clone(), white);
+    world.push(short_box);
+    world.push(tall_box);
+
+    world
+}
+
+/// The Cornell box with its blocks made of smoke, under a larger, dimmer light.
+fn cornell_smoke() -> Vec<Shape> {
+    let white = Material::lambertian(0.73, 0.73, 0.73);
+    let light = Material::diffuse_light(Texture::constant(7.0, 7.0, 7.0));
+
+    let mut world = cornell_room(white);
+    world.push(Shape::xz_rect(113.0, 443.0, 127.0, 432.0, 554.0, light));
+
+    let (short_box, tall_box) = cornell_blocks(
+        Material::isotropic(Texture::constant(1.0, 1.0, 1.0)),
+        Material::isotropic(Texture::constant(0.0, 0.0, 0.0)),
+    );
+    world.push(Shape::constant_medium(short_box, 0.01));
+    world.push(Shape::constant_medium(tall_box, 0.01));
+
+    world
+}
+
+/// The walls, floor and ceiling of the Cornell box, open at the front.
+fn cornell_room(white: Material) -> Vec<Shape> {
+    let red = Material::lambertian(0.65, 0.05, 0.05);
+    let green = Material::lambertian(0.12, 0.45, 0.15);
+
     let left_wall = Shape::yz_rect(0.0, 555.0, 0.0, 555.0, 555.0, green);
     let right_wall = Shape::yz_rect(0.0, 555.0, 0.0, 555.0, 0.0, red);
 
-    let light = Shape::xz_rect(213.0, 343.0, 227.0, 332.0, 554.0, light);
-
     let floor = Shape::xz_rect(0.0, 555.0, 0.0, 555.0, 0.0, white.clone());
     let ceiling = Shape::xz_rect(0.0, 555.0, 0.0, 555.0, 555.0, white.clone());
-    let back_wall = Shape::xy_rect(0.0, 555.0, 0.0, 555.0, 555.0, white.clone());
+    let back_wall = Shape::xy_rect(0.0, 555.0, 0.0, 555.0, 555.0, white);
+
+    vec![left_wall, right_wall, floor, ceiling, back_wall]
+}
+
+/// The short and the tall block standing in the Cornell box.
+fn cornell_blocks(short_material: Material, tall_material: Material) -> (Shape, Shape) {
+    let short_box = Shape::transformed(
+        Shape::cube(0.0, 0.0, 0.0, 165.0, 165.0, 165.0, short_material),
+        Transform::from_parts(
+            &Vec3::new(130.0, 0.0, 65.0),
+            &Vec3::new(0.0, -18.0, 0.0),
+            &Vec3::new(1.0, 1.0, 1.0),
+        )
+        .expect("rotations can be inverted"),
+    );
+    let tall_box = Shape::transformed(
+        Shape::cube(0.0, 0.0, 0.0, 165.0, 330.0, 165.0, tall_material),
+        Transform::from_parts(
+            &Vec3::new(265.0, 0.0, 295.0),
+            &Vec3::new(0.0, 15.0, 0.0),
+            &Vec3::new(1.0, 1.0, 1.0),
+        )
+        .expect("rotations can be inverted"),
+    );
+
+    (short_box, tall_box)
+}
+
+fn custom_world<T: Rng>(
+    rng: &mut T,
+    scene_config: &SceneConfig,
+    split_method: SplitMethod,
+    time_initial: f64,
+    time_final: f64,
+) -> Result<Vec<Shape>> {
+    let mut textures = HashMap::new();
+    for id in scene_config.textures().keys() {
+        resolve_texture(rng, scene_config, id, &mut textures, &mut Vec::new())?;
+    }
+
+    let materials = scene_config
+        .materials()
+        .iter()
+        .map(|(id, material_config)| {
+            let material = build_material(id, material_config, &textures)?;
+            Ok((id.as_str(), material))
+        })
+        .collect::<Result<HashMap<&str, Material>>>()?;
+
+    let mut prototypes = HashMap::new();
+    for (id, prototype_config) in scene_config.prototypes() {
+        let mut shapes = Vec::with_capacity(prototype_config.objects().len());
+        for object_config in prototype_config.objects() {
+            if let ShapeConfig::Instance { .. } = object_config.shape() {
+                let message = format!("Prototype `{}` cannot contain instances", id);
+                return Err(Error::scene(message));
+            }
+
+            // Prototypes are bounded once, so whatever moved in them would be
+            // clipped in later frames of an animation
+            if object_config.motion().is_some() {
+                let message = format!("Prototype `{}` cannot contain motion", id);
+                return Err(Error::scene(message));
+            }
 
-    let box_a = Shape::cube(130.0, 0.0, 65.0, 295.0, 165.0, 230.0, white.clone());
-    let box_b = Shape::cube(265.0, 0.0, 295.0, 430.0, 330.0, 460.0, white);
+            add_object(
+                rng,
+                object_config,
+                &materials,
+                &HashMap::new(),
+                split_method,
+                time_initial,
+                time_final,
+                &mut shapes,
+            )?;
+        }
+
+        if shapes.is_empty() {
+            return Err(Error::scene(format!("Prototype `{}` has no objects", id)));
+        }
+
+        let prototype =
+            BoundingVolumeHierarchy::new(rng, shapes, split_method, time_initial, time_final);
+        prototypes.insert(id.as_str(), Arc::new(prototype));
+    }
+
+    let mut world = Vec::with_capacity(scene_config.objects().len());
+    for object_config in scene_config.objects() {
+        add_object(
+            rng,
+            object_config,
+            &materials,
+            &prototypes,
+            split_method,
+            time_initial,
+            time_final,
+            &mut world,
+        )?;
+    }
+
+    Ok(world)
+}
+
+/// Builds an object, fills it with a medium if it has a density and sets it in
+/// motion if it has any.
+fn add_object<T: Rng>(
+    rng: &mut T,
+    object_config: &ObjectConfig,
+    materials: &HashMap<&str, Material>,
+    prototypes: &HashMap<&str, Arc<BoundingVolumeHierarchy>>,
+    split_method: SplitMethod,
+    time_initial: f64,
+    time_final: f64,
+    world: &mut Vec<Shape>,
+) -> Result<()> {
+    let transform = build_transform(object_config)?;
+    if object_config.motion().is_none() && object_config.density().is_none() {
+        return build_object(object_config, materials, prototypes, transform, world);
+    }
+
+    if object_config.density_field().is_some() && object_config.density().is_none() {
+        return Err(Error::scene("A density field needs a density to scale it"));
+    }
+
+    if let Some(density) = object_config.density() {
+        if !(density > 0.0 && density.is_finite()) {
+            let message = format!("Density {} must be a positive number", density);
+            return Err(Error::scene(message));
+        }
+    }
+
+    // Media are filled in the object's own space and placed along with it, so
+    // their density turns, scales and moves with the object
+    let (transform, placement) = match object_config.density() {
+        Some(_) => (None, transform),
+        None => (transform, None),
+    };
+
+    let mut shapes = Vec::new();
+    build_object(object_config, materials, prototypes, transform, &mut shapes)?;
 
-    vec![
-        left_wall, right_wall, light, floor, ceiling, back_wall, box_a, box_b,
-    ]
+    // Meshes come out as many triangles, which move and bound a medium as one instance
+    let mut shape = if shapes.len() == 1 {
+        shapes.remove(0)
+    } else {
+        let mesh =
+            BoundingVolumeHierarchy::new(rng, shapes, split_method, time_initial, time_final);
+        Shape::instance(Arc::new(mesh), Transform::default(), None)
+    };
+
+    if let Some(density) = object_config.density() {
+        shape = match object_config.density_field() {
+            Some(field_config) => {
+                let field = build_density_field(rng, field_config, &shape, time_initial)?;
+                Shape::heterogeneous_medium(shape, field, density)
+            }
+            None => Shape::constant_medium(shape, density),
+        };
+    }
+
+    if let Some(placement) = placement {
+        shape = Shape::transformed(shape, placement);
+    }
+
+    if let Some(motion_config) = object_config.motion() {
+        shape = build_moving::<T>(shape, motion_config, time_initial, time_final)?;
+    }
+
+    world.push(shape);
+    Ok(())
+}
+
+fn build_density_field<T: Rng>(
+    rng: &mut T,
+    field_config: &DensityFieldConfig,
+    boundary: &Shape,
+    time_initial: f64,
+) -> Result<DensityField> {
+    let field = match field_config {
+        DensityFieldConfig::Turbulence { scale, depth } => {
+            DensityField::turbulence(rng, *scale, *depth)
+        }
+        DensityFieldConfig::Grid { path } => {
+            // The boundary hasn't been placed yet, so these are its own bounds
+            let bounded: &dyn Hittable<'_, T> = boundary;
+            let bounds = bounded
+                .bounding_box(time_initial, time_initial)
+                .ok_or_else(|| {
+                    Error::scene("Can't fit a voxel grid to an object without bounds")
+                })?;
+            DensityField::grid(VoxelGrid::open(path, bounds)?)
+        }
+    };
+
+    Ok(field)
+}
+
+fn build_moving<T: Rng>(
+    shape: Shape,
+    motion_config: &MotionConfig,
+    time_initial: f64,
+    time_final: f64,
+) -> Result<Shape> {
+    if motion_config.keyframes().is_empty() {
+        return Err(Error::scene("Motion needs at least one keyframe"));
+    }
+
+    for keyframe in motion_config.keyframes() {
+        if !keyframe.time().is_finite() {
+            let message = format!("Motion keyframe time {} isn't a number", keyframe.time());
+            return Err(Error::scene(message));
+        }
+
+        if !(keyframe.translate().is_finite() && keyframe.rotate().is_finite()) {
+            let message = format!(
+                "Motion keyframe at {} must have finite translate and rotate",
+                keyframe.time()
+            );
+            return Err(Error::scene(message));
+        }
+
+        if !keyframe.curve().is_valid() {
+            let message = format!(
+                "Motion keyframe at {} has bezier control points outside 0-1",
+                keyframe.time()
+            );
+            return Err(Error::scene(message));
+        }
+    }
+
+    let pivot = match motion_config.pivot() {
+        Some(pivot) if !pivot.is_finite() => {
+            let message = format!("Motion pivot must be finite, got {:?}", pivot);
+            return Err(Error::scene(message));
+        }
+        Some(pivot) => pivot.clone(),
+        None => {
+            let bounded: &dyn Hittable<'_, T> = &shape;
+            let aabb = bounded
+                .bounding_box(time_initial, time_final)
+                .ok_or_else(|| Error::scene("Can't move an object without bounds"))?;
+            0.5 * (aabb.min() + aabb.max())
+        }
+    };
+
+    let keyframes = motion_config
+        .keyframes()
+        .iter()
+        .map(|keyframe| {
+            Keyframe::new(
+                keyframe.time(),
+                keyframe.translate().clone(),
+                keyframe.rotate().clone(),
+            )
+            .curve(keyframe.curve())
+        })
+        .collect();
+
+    Ok(Shape::moving(shape, keyframes, pivot))
+}
+
+fn resolve_texture<'a, T: Rng>(
+    rng: &mut T,
+    scene_config: &'a SceneConfig,
+    id: &'a str,
+    textures: &mut HashMap<&'a str, Texture>,
+    visiting: &mut Vec<&'a str>,
+) -> Result<Texture> {
+    if let Some(texture) = textures.get(id) {
+        return Ok(texture.clone());
+    }
+
+    if visiting.contains(&id) {
+        return Err(Error::scene(format!("Texture `{}` references itself", id)));
+    }
+
+    let (id, texture_config) = scene_config
+        .textures()
+        .get_key_value(id)
+        .ok_or_else(|| Error::scene(format!("Unknown texture `{}`", id)))?;
+
+    visiting.push(id);
+    let texture = match texture_config {
+        TextureConfig::Constant { color } => Texture::constant(color.r(), color.g(), color.b()),
+        TextureConfig::Checker { even, odd } => {
+            let even = resolve_texture(rng, scene_config, even, textures, visiting)?;
+            let odd = resolve_texture(rng, scene_config, odd, textures, visiting)?;
+            Texture::checker(even, odd)
+        }
+        TextureConfig::Noise { scale } => Texture::scaled_noise(rng, *scale),
+        TextureConfig::Image { path } => Texture::image(path)?,
+    };
+    visiting.pop();
+
+    textures.insert(id, texture.clone());
+    Ok(texture)
+}
+
+fn build_material(
+    id: &str,
+    material_config: &MaterialConfig,
+    textures: &HashMap<&str, Texture>,
+) -> Result<Material> {
+    let texture = |color: &Option<Vec3>, texture: &Option<String>| match (color, texture) {
+        (Some(color), None) => Ok(Texture::constant(color.r(), color.g(), color.b())),
+        (None, Some(texture)) => textures.get(texture.as_str()).cloned().ok_or_else(|| {
+            let message = format!("Material `{}` uses unknown texture `{}`", id, texture);
+            Error::scene(message)
+        }),
+        _ => {
+            let message = format!(
+                "Material `{}` must set exactly one of a color or a texture",
+                id
+            );
+            Err(Error::scene(message))
+        }
+    };
+
+    let material = match material_config {
+        MaterialConfig::Lambertian {
+            albedo,
+            texture: id,
+        } => Material::textured(texture(albedo, id)?),
+        MaterialConfig::Metal { albedo, fuzziness } => {
+            Material::metal(albedo.r(), albedo.g(), albedo.b(), *fuzziness)
+        }
+        MaterialConfig::Dielectric { ref_idx } => Material::dielectric(*ref_idx),
+        MaterialConfig::DiffuseLight { emit, texture: id } => {
+            Material::diffuse_light(texture(emit, id)?)
+        }
+        MaterialConfig::Isotropic {
+            albedo,
+            texture: id,
+        } => Material::isotropic(texture(albedo, id)?),
+        MaterialConfig::HenyeyGreenstein {
+            albedo,
+            texture: texture_id,
+            anisotropy,
+        } => {
+            if !(-1.0..=1.0).contains(anisotropy) {
+                let message = format!(
+                    "Material `{}` has anisotropy {}, which must be between -1 and 1",
+                    id, anisotropy
+                );
+                return Err(Error::scene(message));
+            }
+
+            Material::henyey_greenstein(texture(albedo, texture_id)?, *anisotropy)
+        }
+    };
+
+    Ok(material)
+}
+
+fn build_transform(object_config: &ObjectConfig) -> Result<Option<Transform>> {
+    let transform = match object_config.transform() {
+        Some(transform_config) => {
+            let parts = [
+                transform_config.translate(),
+                transform_config.rotate(),
+                transform_config.scale(),
+            ];
+            if let Some(part) = parts.iter().find(|part| !part.is_finite()) {
+                let message = format!("Transform values must be finite, got {:?}", part);
+                return Err(Error::scene(message));
+            }
+
+            let scale = transform_config.scale();
+            let transform = Transform::from_parts(
+                transform_config.translate(),
+                transform_config.rotate(),
+                scale,
+            )
+            .ok_or_else(|| {
+                let message = format!("Transform scale {:?} flattens the object", scale);
+                Error::scene(message)
+            })?;
+
+            Some(transform)
+        }
+        None => None,
+    };
+
+    Ok(transform)
+}
+
+/// Builds the shapes for an object, placed by `transform`.
+fn build_object(
+    object_config: &ObjectConfig,
+    materials: &HashMap<&str, Material>,
+    prototypes: &HashMap<&str, Arc<BoundingVolumeHierarchy>>,
+    transform: Option<Transform>,
+    world: &mut Vec<Shape>,
+) -> Result<()> {
+    let material = |id: &str| {
+        materials
+            .get(id)
+            .cloned()
+            .ok_or_else(|| Error::scene(format!("Unknown material `{}`", id)))
+    };
+
+    let shape = match object_config.shape() {
+        ShapeConfig::Sphere {
+            center,
+            radius,
+            material: id,
+        } => Shape::sphere(center.x(), center.y(), center.z(), *radius, material(id)?),
+        ShapeConfig::MovingSphere {
+            center_start,
+            center_end,
+            radius,
+            material: id,
+            time_start,
+            time_end,
+        } => Shape::moving_sphere(
+            center_start.x(),
+            center_start.y(),
+            center_start.z(),
+            center_end.x(),
+            center_end.y(),
+            center_end.z(),
+            *radius,
+            material(id)?,
+            *time_start,
+            *time_end,
+        ),
+        ShapeConfig::XyRect {
+            x0,
+            x1,
+            y0,
+            y1,
+            k,
+            material: id,
+        } => Shape::xy_rect(*x0, *x1, *y0, *y1, *k, material(id)?),
+        ShapeConfig::XzRect {
+            x0,
+            x1,
+            z0,
+            z1,
+            k,
+            material: id,
+        } => Shape::xz_rect(*x0, *x1, *z0, *z1, *k, material(id)?),
+        ShapeConfig::YzRect {
+            y0,
+            y1,
+            z0,
+            z1,
+            k,
+            material: id,
+        } => Shape::yz_rect(*y0, *y1, *z0, *z1, *k, material(id)?),
+        ShapeConfig::Cube {
+            min,
+            max,
+            material: id,
+        } => Shape::cube(
+            min.x(),
+            min.y(),
+            min.z(),
+            max.x(),
+            max.y(),
+            max.z(),
+            material(id)?,
+        ),
+        ShapeConfig::Triangle {
+            v0,
+            v1,
+            v2,
+            material: id,
+        } => Shape::triangle(v0.clone(), v1.clone(), v2.clone(), material(id)?),
+        ShapeConfig::Mesh {
+            positions,
+            indices,
+            material: id,
+        } => {
+            if let Some(index) = indices.iter().flatten().find(|&&i| i >= positions.len()) {
+                let message = format!(
+                    "Mesh index {} is out of range for {} positions",
+                    index,
+                    positions.len()
+                );
+                return Err(Error::scene(message));
+            }
+
+            let faces = indices
+                .iter()
+                .map(|&positions| Face::new(positions, None, None, 0))
+                .collect();
+            let mesh = Mesh::new(
+                positions.clone(),
+                Vec::new(),
+                Vec::new(),
+                faces,
+                vec![material(id)?],
+            )?;
+
+            add_mesh(mesh, transform.as_ref(), world);
+            return Ok(());
+        }
+        ShapeConfig::Obj { path, material: id } => {
+            let default_material = match id {
+                Some(id) => material(id)?,
+                None => Material::lambertian(0.8, 0.8, 0.8),
+            };
+
+            let mesh = obj::load(path, default_material)?;
+
+            add_mesh(mesh, transform.as_ref(), world);
+            return Ok(());
+        }
+        ShapeConfig::Instance {
+            prototype: id,
+            material: material_id,
+        } => {
+            let prototype = prototypes
+                .get(id.as_str())
+                .cloned()
+                .ok_or_else(|| Error::scene(format!("Unknown prototype `{}`", id)))?;
+            let material = material_id.as_deref().map(material).transpose()?;
+
+            // Instances carry their own transform, so don't wrap them again below
+            let instance = Shape::instance(prototype, transform.unwrap_or_default(), material);
+            world.push(instance);
+            return Ok(());
+        }
+    };
+
+    let shape = match transform {
+        Some(transform) => Shape::transformed(shape, transform),
+        None => shape,
+    };
+
+    world.push(shape);
+    Ok(())
+}
+
+fn add_mesh(mut mesh: Mesh, transform: Option<&Transform>, world: &mut Vec<Shape>) {
+    if let Some(transform) = transform {
+        mesh.transform(transform);
+    }
+
+    world.extend(Shape::mesh(mesh));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+    use crate::ray::Ray;
+
+    /// A unit cube of smoke, dense in its lower x half and empty in the upper,
+    /// placed by the `transform` and `motion` in `placement`.
+    fn half_smoke(name: &str, placement: &str) -> Shape {
+        let mut grid = b"VOL\x03".to_vec();
+        for word in &[1_u32, 4, 1, 1, 1] {
+            grid.extend_from_slice(&word.to_le_bytes());
+        }
+        grid.extend_from_slice(&[0; 24]);
+        for value in &[1.0_f32, 1.0, 0.0, 0.0] {
+            grid.extend_from_slice(&value.to_le_bytes());
+        }
+        let path = env::temp_dir().join(format!("ray_tracer_world_{}.vol", name));
+        fs::write(&path, grid).unwrap();
+
+        let object_config: ObjectConfig = toml::from_str(&format!(
+            r#"
+            type = "cube"
+            min = {{ x = 0.0, y = 0.0, z = 0.0 }}
+            max = {{ x = 1.0, y = 1.0, z = 1.0 }}
+            material = "smoke"
+            density = 50.0
+            density_field = {{ type = "grid", path = {:?} }}
+            {}
+            "#,
+            path.display().to_string(),
+            placement
+        ))
+        .unwrap();
+
+        let mut materials = HashMap::new();
+        materials.insert(
+            "smoke",
+            Material::isotropic(Texture::constant(1.0, 1.0, 1.0)),
+        );
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut world = Vec::new();
+        add_object(
+            &mut rng,
+            &object_config,
+            &materials,
+            &HashMap::new(),
+            SplitMethod::Sah,
+            0.0,
+            1.0,
+            &mut world,
+        )
+        .unwrap();
+
+        assert_eq!(world.len(), 1);
+        world.remove(0)
+    }
+
+    /// How much light gets through the smoke straight along z at `x`.
+    fn transmittance(smoke: &Shape, x: f64, time: f64) -> f64 {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let ray = Ray::new(Vec3::new(x, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0), time);
+        let shape: &dyn Hittable<'_, SmallRng> = smoke;
+        shape.transmittance(&ray, 0.0, f64::INFINITY, &mut rng)
+    }
+
+    #[test]
+    fn media_fill_the_object_before_it_is_placed() {
+        let smoke = half_smoke(
+            "placed",
+            "transform = { translate = { x = 10.0, y = 0.0, z = 0.0 } }",
+        );
+
+        assert!(transmittance(&smoke, 10.1, 0.0) < 1e-6);
+        assert_eq!(transmittance(&smoke, 10.9, 0.0), 1.0);
+        assert_eq!(transmittance(&smoke, 0.1, 0.0), 1.0);
+    }
+
+    #[test]
+    fn media_carry_their_density_as_they_move() {
+        let smoke = half_smoke(
+            "moving",
+            r#"
+            transform = { translate = { x = 10.0, y = 0.0, z = 0.0 } }
+            motion = { keyframes = [
+                { time = 0.0 },
+                { time = 1.0, translate = { x = 5.0, y = 0.0, z = 0.0 } },
+            ] }
+            "#,
+        );
+
+        for &(time, x) in &[(0.0, 10.0), (0.5, 12.5), (1.0, 15.0)] {
+            assert!(transmittance(&smoke, x + 0.1, time) < 1e-6, "at {}", time);
+            assert_eq!(transmittance(&smoke, x + 0.9, time), 1.0, "at {}", time);
+        }
+    }
 }
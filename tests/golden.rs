@@ -0,0 +1,225 @@
+//! Renders each world preset at a low resolution with a fixed seed and compares
+//! the result against the reference images in `tests/golden`.
+//!
+//! After a change that's meant to alter the output, run with `BLESS=1` to
+//! overwrite the references and check the new images in.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use image::RgbImage;
+use toml::Value;
+
+/// Root mean square error, in 8-bit levels, allowed before a render counts as changed.
+const MAX_RMSE: f64 = 1.0;
+
+const RENDERER: &str = r#"
+screen_width = 96
+screen_height = 64
+antialias_iterations = 16
+render_parallel = true
+use_bounding_volume = true
+seed = 1
+"#;
+
+const OUTDOOR_CAMERA: &str = r#"
+[camera]
+fov = 20.0
+aperture = 0.0
+look_from = { x = 13.0, y = 2.0, z = 3.0 }
+look_at = { x = 0.0, y = 0.0, z = 0.0 }
+
+[background]
+type = "gradient"
+"#;
+
+const CORNELL_CAMERA: &str = r#"
+[camera]
+fov = 40.0
+aperture = 0.0
+look_from = { x = 278.0, y = 278.0, z = -800.0 }
+look_at = { x = 278.0, y = 278.0, z = 0.0 }
+"#;
+
+#[test]
+fn basic() {
+    check(
+        "basic",
+        &format!("[world]\nscene = \"Basic\"\n{}", OUTDOOR_CAMERA),
+    );
+}
+
+#[test]
+fn dynamic() {
+    let scene = format!(
+        "[world]\nscene = \"Dynamic\"\nmax_objects = 100\n{}",
+        OUTDOOR_CAMERA
+    );
+    check("dynamic", &scene);
+}
+
+#[test]
+fn checker() {
+    check(
+        "checker",
+        &format!("[world]\nscene = \"Checker\"\n{}", OUTDOOR_CAMERA),
+    );
+}
+
+#[test]
+fn perlin() {
+    check(
+        "perlin",
+        &format!("[world]\nscene = \"Perlin\"\n{}", OUTDOOR_CAMERA),
+    );
+}
+
+#[test]
+fn earth() {
+    check(
+        "earth",
+        &format!("[world]\nscene = \"Earth\"\n{}", OUTDOOR_CAMERA),
+    );
+}
+
+#[test]
+fn simple_light() {
+    check(
+        "simple_light",
+        r#"
+[world]
+scene = "SimpleLight"
+
+[camera]
+fov = 20.0
+aperture = 0.0
+look_from = { x = 26.0, y = 3.0, z = 6.0 }
+look_at = { x = 0.0, y = 2.0, z = 0.0 }
+"#,
+    );
+}
+
+#[test]
+fn cornell_box() {
+    check(
+        "cornell_box",
+        &format!("[world]\nscene = \"CornellBox\"\n{}", CORNELL_CAMERA),
+    );
+}
+
+#[test]
+fn cornell_smoke() {
+    check(
+        "cornell_smoke",
+        &format!("[world]\nscene = \"CornellSmoke\"\n{}", CORNELL_CAMERA),
+    );
+}
+
+#[test]
+fn custom() {
+    let scene = fs::read_to_string(manifest_dir().join("resources/custom.toml")).unwrap();
+    check("custom", &scene);
+}
+
+/// Renders `scene` with the shared renderer settings and compares it against
+/// the reference image called `name`.
+fn check(name: &str, scene: &str) {
+    let output_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    fs::create_dir_all(&output_dir).unwrap();
+
+    let actual_path = output_dir.join(format!("{}.png", name));
+    let config_path = output_dir.join(format!("{}.toml", name));
+
+    let mut config: Value = scene.parse().unwrap();
+    let table = config.as_table_mut().unwrap();
+    table.insert("renderer".to_string(), RENDERER.parse().unwrap());
+
+    let mut output = toml::map::Map::new();
+    output.insert(
+        "output_path".to_string(),
+        Value::String(actual_path.display().to_string()),
+    );
+    table.insert("output".to_string(), Value::Table(output));
+
+    fs::write(&config_path, toml::to_string(&config).unwrap()).unwrap();
+
+    let result = Command::new(env!("CARGO_BIN_EXE_ray_tracer"))
+        .current_dir(manifest_dir())
+        .env("TRACER_CONFIG_PATH", &config_path)
+        .output()
+        .unwrap();
+    assert!(
+        result.status.success(),
+        "rendering {} failed:\n{}",
+        name,
+        String::from_utf8_lossy(&result.stderr)
+    );
+
+    let reference_path = manifest_dir()
+        .join("tests/golden")
+        .join(format!("{}.png", name));
+    if env::var_os("BLESS").is_some() {
+        fs::copy(&actual_path, &reference_path).unwrap();
+        return;
+    }
+
+    let actual = image::open(&actual_path).unwrap().to_rgb8();
+    let reference = match image::open(&reference_path) {
+        Ok(reference) => reference.to_rgb8(),
+        Err(e) => panic!(
+            "no reference image at {} ({}), run with BLESS=1 to create it",
+            reference_path.display(),
+            e
+        ),
+    };
+    assert_eq!(
+        actual.dimensions(),
+        reference.dimensions(),
+        "{} changed size",
+        name
+    );
+
+    let rmse = rmse(&actual, &reference);
+    if rmse > MAX_RMSE {
+        let diff_path = output_dir.join(format!("{}-diff.png", name));
+        diff(&actual, &reference).save(&diff_path).unwrap();
+
+        panic!(
+            "{} differs from its reference by an RMSE of {:.3}\n  actual: {}\n  diff:   {}",
+            name,
+            rmse,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+fn manifest_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn rmse(a: &RgbImage, b: &RgbImage) -> f64 {
+    let squared_error: f64 = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(&a, &b)| (a as f64 - b as f64).powi(2))
+        .sum();
+
+    (squared_error / a.as_raw().len() as f64).sqrt()
+}
+
+/// Absolute difference per channel, brightened so small changes stand out.
+fn diff(a: &RgbImage, b: &RgbImage) -> RgbImage {
+    let mut diff = a.clone();
+    for (pixel, other) in diff.pixels_mut().zip(b.pixels()) {
+        for (channel, other) in pixel.0.iter_mut().zip(other.0.iter()) {
+            *channel = channel.abs_diff(*other).saturating_mul(4);
+        }
+    }
+
+    diff
+}